@@ -5,6 +5,7 @@ extern crate image;
 
 use std::cmp::min;
 use std::convert::TryInto;
+use std::sync::Arc;
 use std::thread;
 use std::time;
 
@@ -18,12 +19,13 @@ use doremi::Buffer;
 use doremi::BufferType;
 use doremi::ConnectorStatus;
 use doremi::Device;
+use doremi::DeviceEnumerator;
 use doremi::Format;
 use doremi::ModeType;
 use doremi::PipelineInit;
 
-struct Image<'a> {
-    buffer:    Buffer<'a>,
+struct Image {
+    buffer:    Arc<Buffer>,
     width:     usize,
     height:    usize,
     display_w: usize,
@@ -37,13 +39,15 @@ fn main() {
         .arg(
             Arg::with_name("device")
                 .short("D")
-                .help("DRM Device Path")
-                .default_value("/dev/dri/card0"),
+                .takes_value(true)
+                .help("DRM Device Path"),
         )
         .arg(Arg::with_name("images").multiple(true).required(true))
         .get_matches();
-    let dev_path = matches.value_of("device").unwrap();
-    let dev = Device::new(dev_path).unwrap();
+    let dev = match matches.value_of("device") {
+        Some(path) => Device::new(path).unwrap(),
+        None => DeviceEnumerator::new().open_first_with_connectors().unwrap(),
+    };
     let img_path = matches.values_of("images").unwrap();
 
     let connectors = dev.get_connectors().unwrap();
@@ -67,7 +71,7 @@ fn main() {
             let img_h = img.height().try_into().unwrap();
             let img_w = img.width().try_into().unwrap();
 
-            let buffer = dev
+            let mut buffer = dev
                 .allocate_buffer(BufferType::Dumb, img_w, img_h, 32)
                 .unwrap()
                 .map()
@@ -92,7 +96,7 @@ fn main() {
             let margin_w = ((mode.width() - display_w) / 2) as isize;
 
             Image {
-                buffer,
+                buffer: Arc::new(buffer),
                 height: img_h,
                 width: img_w,
                 display_h,
@@ -108,7 +112,7 @@ fn main() {
         .discover(connector)
         .unwrap()
         .set_mode(mode)
-        .set_buffer(&first.buffer)
+        .set_buffer(first.buffer.clone())
         .set_plane_coordinates(first.margin_w, first.margin_h)
         .set_plane_dimensions(first.display_w, first.display_h)
         .build()
@@ -122,7 +126,7 @@ fn main() {
         let image = &images[index % images.len()];
 
         pipeline = pipeline
-            .update_buffer(&image.buffer)
+            .update_buffer(image.buffer.clone())
             .unwrap()
             .update_plane_size(image.width, image.height)
             .unwrap()
@@ -1,57 +1,199 @@
-use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
 
+use crate::blob::PropertyBlob;
+use crate::connector::ConnectorType;
 use crate::device::Device;
+use crate::error::property_not_found;
 use crate::error::Error;
 use crate::error::Result;
 use crate::object::Object;
+use crate::object::ObjectType;
+use crate::property::Property;
+use crate::property::PropertyKind;
+use crate::property::PropertyValue;
+use crate::rawdevice::DRM_MODE_ATOMIC_ALLOW_MODESET;
+use crate::rawdevice::DRM_MODE_ATOMIC_NONBLOCK;
+use crate::rawdevice::DRM_MODE_ATOMIC_TEST_ONLY;
+use crate::rawdevice::DRM_MODE_PAGE_FLIP_ASYNC;
+use crate::rawdevice::DRM_MODE_PAGE_FLIP_EVENT;
 
 #[derive(Clone)]
 #[derive(Copy)]
 #[derive(Debug)]
-pub(crate) struct AtomicProperty {
-    object_id:   u32,
-    property_id: u32,
-    value:       u64,
+#[derive(PartialEq)]
+pub enum CheckResult {
+    Accepted,
+    Rejected,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct AtomicRejection {
+    object_id:     u32,
+    object_name:   String,
+    property_id:   u32,
+    property_name: String,
+    value:         u64,
 }
 
-impl PartialEq for AtomicProperty {
-    fn eq(&self, other: &Self) -> bool {
-        (self.object_id == other.object_id) &&
-            (self.property_id == other.property_id)
+impl fmt::Display for AtomicRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} = {}",
+            self.object_name, self.property_name, self.value
+        )
     }
 }
 
-impl Eq for AtomicProperty {}
+impl AtomicRejection {
+    pub fn get_object_id(&self) -> u32 {
+        self.object_id
+    }
 
-impl Ord for AtomicProperty {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let primary = self.object_id.cmp(&other.object_id);
+    pub fn get_object_name(&self) -> &str {
+        &self.object_name
+    }
 
-        if primary != Ordering::Equal {
-            return primary;
-        }
+    pub fn get_property_id(&self) -> u32 {
+        self.property_id
+    }
 
-        self.property_id.cmp(&other.property_id)
+    pub fn get_property_name(&self) -> &str {
+        &self.property_name
     }
+
+    pub fn get_value(&self) -> u64 {
+        self.value
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum AtomicDiagnosis {
+    Accepted,
+    Rejected(AtomicRejection),
+    // The driver only turns down a combination of several properties
+    Inconclusive,
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+pub struct CommitOptions {
+    allow_modeset:   bool,
+    nonblocking:     bool,
+    page_flip_async: bool,
+    page_flip_event: bool,
+    test_only:       bool,
+    user_data:       u64,
 }
 
-impl PartialOrd for AtomicProperty {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let primary = self.object_id.cmp(&other.object_id);
+impl CommitOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub(crate) fn get_flags(&self) -> u32 {
+        let mut flags = 0;
+
+        if self.allow_modeset {
+            flags |= DRM_MODE_ATOMIC_ALLOW_MODESET;
+        }
+
+        if self.nonblocking {
+            flags |= DRM_MODE_ATOMIC_NONBLOCK;
+        }
+
+        if self.page_flip_async {
+            flags |= DRM_MODE_PAGE_FLIP_ASYNC;
+        }
+
+        if self.page_flip_event {
+            flags |= DRM_MODE_PAGE_FLIP_EVENT;
+        }
 
-        if primary != Ordering::Equal {
-            return Some(primary);
+        if self.test_only {
+            flags |= DRM_MODE_ATOMIC_TEST_ONLY;
         }
 
-        Some(self.property_id.cmp(&other.property_id))
+        flags
+    }
+
+    pub fn get_user_data(&self) -> u64 {
+        self.user_data
+    }
+
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking
+    }
+
+    pub fn set_allow_modeset(mut self, allow: bool) -> CommitOptions {
+        self.allow_modeset = allow;
+        self
+    }
+
+    pub fn set_nonblocking(mut self, nonblocking: bool) -> CommitOptions {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    pub fn set_page_flip_async(mut self, flip_async: bool) -> CommitOptions {
+        self.page_flip_async = flip_async;
+        self
+    }
+
+    pub fn set_page_flip_event(mut self, event: bool) -> CommitOptions {
+        self.page_flip_event = event;
+        self
+    }
+
+    pub(crate) fn set_test_only(mut self, test_only: bool) -> CommitOptions {
+        self.test_only = test_only;
+        self
+    }
+
+    // Handed back in the flip complete event
+    pub fn set_user_data(mut self, user_data: u64) -> CommitOptions {
+        self.user_data = user_data;
+        self
     }
 }
 
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct AtomicProperty {
+    object_id:   u32,
+    object_type: ObjectType,
+    property_id: u32,
+    value:       u64,
+}
+
 impl AtomicProperty {
+    fn new(object: &impl Object, property_id: u32, value: u64) -> Self {
+        AtomicProperty {
+            object_id: object.get_id(),
+            object_type: object.get_type(),
+            property_id,
+            value,
+        }
+    }
+
     pub fn get_object_id(&self) -> u32 {
         self.object_id
     }
 
+    pub fn get_object_type(&self) -> ObjectType {
+        self.object_type
+    }
+
     pub fn get_property_id(&self) -> u32 {
         self.property_id
     }
@@ -59,66 +201,493 @@ impl AtomicProperty {
     pub fn get_value(&self) -> u64 {
         self.value
     }
+
+    fn is_same(&self, other: &AtomicProperty) -> bool {
+        self.object_id == other.object_id &&
+            self.property_id == other.property_id
+    }
 }
 
+#[derive(Clone)]
 #[derive(Debug)]
-pub(crate) struct AtomicRequest<'a> {
-    dev:        &'a Device,
+pub struct AtomicRequest {
+    dev:        Device,
     properties: Vec<AtomicProperty>,
+
+    // Blobs created for this request only, destroyed along with it
+    blobs: Vec<Arc<PropertyBlob>>,
 }
 
-impl<'a> AtomicRequest<'a> {
-    pub fn new(dev: &'a Device) -> Self {
+impl AtomicRequest {
+    pub fn new(dev: &Device) -> Self {
         AtomicRequest {
-            dev,
+            dev: dev.clone(),
             properties: Vec::new(),
+            blobs: Vec::new(),
         }
     }
 
+    fn add(&mut self, property: AtomicProperty) -> Result<()> {
+        if self.properties.iter().any(|prop| prop.is_same(&property)) {
+            return Err(Error::DuplicatePropertyError {
+                object_id:   property.object_id,
+                property_id: property.property_id,
+            });
+        }
+
+        self.properties.push(property);
+        Ok(())
+    }
+
+    pub(crate) fn add_blob(&mut self, blob: PropertyBlob) {
+        self.blobs.push(Arc::new(blob));
+    }
+
+    pub fn add_bitmask_property(
+        self,
+        object: &impl Object,
+        property: &str,
+        values: &[&str],
+    ) -> Result<AtomicRequest> {
+        let names = values.iter().map(|name| name.to_string()).collect();
+
+        self.add_property_value(object, property, PropertyValue::Bitmask(names))
+    }
+
+    pub fn add_blob_property(
+        self,
+        object: &impl Object,
+        property: &str,
+        blob: &PropertyBlob,
+    ) -> Result<AtomicRequest> {
+        let id = blob.get_id();
+
+        self.add_property_value(object, property, PropertyValue::Blob(id))
+    }
+
+    pub fn add_enum_property(
+        self,
+        object: &impl Object,
+        property: &str,
+        value: &str,
+    ) -> Result<AtomicRequest> {
+        let name = value.to_string();
+
+        self.add_property_value(object, property, PropertyValue::Enum(name))
+    }
+
+    pub fn add_object_property(
+        self,
+        object: &impl Object,
+        property: &str,
+        value: &impl Object,
+    ) -> Result<AtomicRequest> {
+        let prop = self.find_property(object, property)?;
+
+        match prop.get_kind() {
+            PropertyKind::Object(type_) if *type_ == value.get_type() => {},
+            _ => return Err(Error::InvalidConfigurationError),
+        }
+
+        let raw = prop.encode(&PropertyValue::Object(value.get_id()))?;
+        self.add_property_id(object, prop.get_id(), raw)
+    }
+
     pub fn add_property(
-        mut self,
+        self,
         object: &impl Object,
         property: &str,
         value: u64,
-    ) -> Result<AtomicRequest<'a>> {
+    ) -> Result<AtomicRequest> {
         let id = object.get_property_id(property)?;
-        let property = AtomicProperty {
-            object_id: object.get_id(),
-            property_id: id,
-            value,
-        };
-        self.properties.push(property);
+
+        self.add_property_id(object, id, value)
+    }
+
+    pub(crate) fn add_property_id(
+        mut self,
+        object: &impl Object,
+        property_id: u32,
+        value: u64,
+    ) -> Result<AtomicRequest> {
+        self.add(AtomicProperty::new(object, property_id, value))?;
         Ok(self)
     }
 
+    pub fn add_property_value(
+        self,
+        object: &impl Object,
+        property: &str,
+        value: PropertyValue,
+    ) -> Result<AtomicRequest> {
+        let prop = self.find_property(object, property)?;
+        let raw = prop.encode(&value)?;
+
+        self.add_property_id(object, prop.get_id(), raw)
+    }
+
+    pub fn add_signed_property(
+        self,
+        object: &impl Object,
+        property: &str,
+        value: i64,
+    ) -> Result<AtomicRequest> {
+        let value = PropertyValue::SignedRange(value);
+
+        self.add_property_value(object, property, value)
+    }
+
     pub fn commit(&self) -> Result<()> {
-        let clone = self.properties.clone();
+        self.commit_with(CommitOptions::new().set_allow_modeset(true))
+    }
 
-        self.dev.atomic_commit(clone)?;
+    pub fn commit_with(&self, options: CommitOptions) -> Result<()> {
+        self.dev.commit(self, options)
+    }
 
-        Ok(())
+    // Finds a property that the driver accepts the rest of the request
+    // without, leaving it to its current value. The request is bisected
+    // as long as one of the halves can be singled out, then each remaining
+    // candidate is tried in turn.
+    pub fn diagnose(&self) -> Result<AtomicDiagnosis> {
+        if self.test()? == CheckResult::Accepted {
+            return Ok(AtomicDiagnosis::Accepted);
+        }
+
+        let mut candidates: Vec<usize> = (0..self.properties.len()).collect();
+
+        while candidates.len() > 1 {
+            let (first, second) = candidates.split_at(candidates.len() / 2);
+
+            if self.test_without(first)? == CheckResult::Accepted {
+                candidates = first.to_vec();
+            } else if self.test_without(second)? == CheckResult::Accepted {
+                candidates = second.to_vec();
+            } else {
+                break;
+            }
+        }
+
+        for idx in candidates {
+            if self.test_without(&[idx])? == CheckResult::Accepted {
+                let rejection = self.get_rejection(&self.properties[idx])?;
+
+                return Ok(AtomicDiagnosis::Rejected(rejection));
+            }
+        }
+
+        Ok(AtomicDiagnosis::Inconclusive)
     }
 
-    pub fn update_property(
+    fn find_property(
+        &self,
+        object: &impl Object,
+        property: &str,
+    ) -> Result<Property> {
+        self.dev
+            .get_properties(object)?
+            .into_iter()
+            .find(|prop| prop.get_name() == property)
+            .ok_or_else(|| property_not_found(object, property))
+    }
+
+    fn get_object_name(&self, property: &AtomicProperty) -> Result<String> {
+        let id = property.object_id;
+
+        let name = match property.object_type {
+            ObjectType::Connector => {
+                let connector =
+                    self.dev.raw().get_connector(id, None, None, None)?;
+
+                let type_ = ConnectorType::from(connector.connector_type);
+
+                format!("{}-{}", type_, connector.connector_type_id)
+            },
+            ObjectType::Crtc => format!("CRTC {}", id),
+            ObjectType::Plane => format!("plane {}", id),
+            type_ => format!("{:?} {}", type_, id),
+        };
+
+        Ok(name)
+    }
+
+    fn get_rejection(
+        &self,
+        property: &AtomicProperty,
+    ) -> Result<AtomicRejection> {
+        let prop = self.dev.get_property(property.property_id)?;
+
+        Ok(AtomicRejection {
+            object_id:     property.object_id,
+            object_name:   self.get_object_name(property)?,
+            property_id:   property.property_id,
+            property_name: prop.get_name().to_string(),
+            value:         property.value,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.properties.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AtomicProperty> {
+        self.properties.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.properties.len()
+    }
+
+    pub fn merge(
+        mut self,
+        other: AtomicRequest,
+    ) -> Result<AtomicRequest> {
+        for property in other.properties {
+            self.add(property)?;
+        }
+
+        self.blobs.extend(other.blobs);
+        Ok(self)
+    }
+
+    fn position(&self, property: &AtomicProperty) -> Result<usize> {
+        self.properties
+            .iter()
+            .position(|prop| prop.is_same(property))
+            .ok_or(Error::PropertyNotQueuedError {
+                object_id:   property.object_id,
+                property_id: property.property_id,
+            })
+    }
+
+    pub fn remove_property(
         mut self,
         object: &impl Object,
         property: &str,
-        value: u64,
-    ) -> Result<AtomicRequest<'a>> {
+    ) -> Result<AtomicRequest> {
         let id = object.get_property_id(property)?;
-        let property = AtomicProperty {
-            object_id: object.get_id(),
-            property_id: id,
-            value,
-        };
+        let property = AtomicProperty::new(object, id, 0);
+
+        let idx = self.position(&property)?;
+        self.properties.remove(idx);
+        Ok(self)
+    }
 
-        let idx = self
+    pub fn test(&self) -> Result<CheckResult> {
+        self.test_with(CommitOptions::new().set_allow_modeset(true))
+    }
+
+    pub fn test_with(&self, options: CommitOptions) -> Result<CheckResult> {
+        match self.dev.commit(self, options.set_test_only(true)) {
+            Ok(_) => Ok(CheckResult::Accepted),
+            Err(Error::InvalidArgumentError(_)) |
+            Err(Error::NoSpaceError(_)) => Ok(CheckResult::Rejected),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn test_without(&self, excluded: &[usize]) -> Result<CheckResult> {
+        let properties: Vec<AtomicProperty> = self
             .properties
             .iter()
-            .position(|prop| prop == &property)
-            .ok_or(Error::UninitializedError)?;
+            .enumerate()
+            .filter(|(idx, _)| !excluded.contains(idx))
+            .map(|(_, prop)| *prop)
+            .collect();
+
+        // Nothing left to change, the current state is obviously fine
+        if properties.is_empty() {
+            return Ok(CheckResult::Accepted);
+        }
 
+        let request = AtomicRequest {
+            dev: self.dev.clone(),
+            properties,
+            blobs: Vec::new(),
+        };
+
+        request.test()
+    }
+
+    pub fn update_property(
+        mut self,
+        object: &impl Object,
+        property: &str,
+        value: u64,
+    ) -> Result<AtomicRequest> {
+        let id = object.get_property_id(property)?;
+        let property = AtomicProperty::new(object, id, value);
+
+        let idx = self.position(&property)?;
         self.properties[idx] = property;
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::simulated::fixtures;
+    use crate::Error;
+    use crate::Object;
+    use crate::PropertyBlob;
+
+    use super::AtomicDiagnosis;
+    use super::AtomicRequest;
+    use super::CheckResult;
+    use super::CommitOptions;
+
+    #[test]
+    fn test_atomic_check() {
+        let dev = fixtures::hdmi_output()
+            .add_overlay_plane(1 << 1)
+            .into_device()
+            .unwrap();
+        let overlay = dev.get_planes().unwrap().pop().unwrap();
+        let buffer = fixtures::framebuffer(&dev);
+
+        let init = fixtures::pipeline_init(&dev, &buffer);
+        assert_eq!(init.check().unwrap(), CheckResult::Accepted);
+
+        // The overlay can't be used with our only CRTC
+        let init = init.set_plane(overlay);
+        assert_eq!(init.check().unwrap(), CheckResult::Rejected);
+        assert!(init.build().is_err());
+    }
+
+    #[test]
+    fn test_atomic_diagnose() {
+        let dev = fixtures::hdmi_device();
+        let plane = dev.get_planes().unwrap().remove(0);
+        let buffer = fixtures::framebuffer(&dev);
+
+        let init = fixtures::pipeline_init(&dev, &buffer);
+        assert_eq!(init.diagnose().unwrap(), AtomicDiagnosis::Accepted);
+
+        let init = init.add_property(&plane, "pixel blend mode", 7).unwrap();
+        let rejection = match init.diagnose().unwrap() {
+            AtomicDiagnosis::Rejected(rejection) => rejection,
+            diagnosis => panic!("Unexpected diagnosis {:?}", diagnosis),
+        };
+
+        assert_eq!(rejection.get_object_id(), plane.get_id());
+        assert_eq!(rejection.get_property_name(), "pixel blend mode");
+        assert_eq!(rejection.get_value(), 7);
+        assert_eq!(
+            rejection.to_string(),
+            format!("plane {}: pixel blend mode = 7", plane.get_id())
+        );
+    }
+
+    #[test]
+    fn test_atomic_request() {
+        let dev = fixtures::hdmi_device();
+
+        let empty = AtomicRequest::new(&dev);
+        assert!(empty.is_empty());
+        dev.commit(&empty, CommitOptions::new()).unwrap();
+
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+        let plane = crtc.get_possible_planes().unwrap().remove(0);
+
+        let first = AtomicRequest::new(&dev)
+            .add_property(&plane, "CRTC_X", 10)
+            .unwrap();
+        assert!(first
+            .clone()
+            .add_property(&plane, "CRTC_X", 20)
+            .is_err());
+
+        let second = AtomicRequest::new(&dev)
+            .add_property(&plane, "CRTC_Y", 10)
+            .unwrap()
+            .add_property(&crtc, "ACTIVE", 0)
+            .unwrap();
+
+        let request = first.clone().merge(second.clone()).unwrap();
+        assert_eq!(request.len(), 3);
+        assert!(request.clone().merge(first).is_err());
+
+        let request = request
+            .update_property(&plane, "CRTC_Y", 20)
+            .unwrap()
+            .remove_property(&crtc, "ACTIVE")
+            .unwrap();
+        let err = request.clone().remove_property(&crtc, "ACTIVE").unwrap_err();
+        assert_eq!(err, Error::PropertyNotQueuedError {
+            object_id:   crtc.get_id(),
+            property_id: crtc.get_property_id("ACTIVE").unwrap(),
+        });
+        assert_eq!(
+            request.clone().update_property(&crtc, "ACTIVE", 1).unwrap_err(),
+            err
+        );
+
+        let values: Vec<_> = request
+            .iter()
+            .map(|prop| (prop.get_object_id(), prop.get_value()))
+            .collect();
+        assert_eq!(values, vec![(plane.get_id(), 10), (plane.get_id(), 20)]);
+
+        dev.commit(&request, CommitOptions::new()).unwrap();
+    }
+
+    #[test]
+    fn test_atomic_typed_properties() {
+        let dev = fixtures::hdmi_device();
+        let connector = dev.get_connectors().unwrap().remove(0);
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+        let plane = dev.get_planes().unwrap().remove(0);
+
+        let mode = connector.get_modes().unwrap().remove(0);
+        let blob = PropertyBlob::from_mode(&dev, &mode).unwrap();
+
+        let rotation = ["rotate-180", "reflect-y"];
+        let request = AtomicRequest::new(&dev)
+            .add_enum_property(&plane, "pixel blend mode", "Coverage")
+            .unwrap()
+            .add_bitmask_property(&plane, "rotation", &rotation)
+            .unwrap()
+            .add_signed_property(&plane, "CRTC_X", -10)
+            .unwrap()
+            .add_object_property(&connector, "CRTC_ID", &crtc)
+            .unwrap()
+            .add_blob_property(&crtc, "MODE_ID", &blob)
+            .unwrap();
+
+        let values: Vec<_> =
+            request.iter().map(|prop| prop.get_value()).collect();
+        assert_eq!(values, vec![
+            2,
+            (1 << 2) | (1 << 5),
+            -10i64 as u64,
+            crtc.get_id() as u64,
+            blob.get_id() as u64,
+        ]);
+
+        let request = AtomicRequest::new(&dev);
+        assert!(request
+            .clone()
+            .add_enum_property(&plane, "pixel blend mode", "Blend")
+            .is_err());
+        assert!(request
+            .clone()
+            .add_bitmask_property(&plane, "rotation", &["rotate-45"])
+            .is_err());
+        assert!(request
+            .clone()
+            .add_signed_property(&plane, "CRTC_X", i64::MAX)
+            .is_err());
+        assert!(request
+            .clone()
+            .add_signed_property(&plane, "SRC_X", 0)
+            .is_err());
+        assert!(request
+            .clone()
+            .add_object_property(&plane, "CRTC_ID", &plane)
+            .is_err());
+        assert!(request
+            .clone()
+            .add_enum_property(&plane, "type", "Overlay")
+            .is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::Debug;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::OwnedFd;
+
+use mmap::MemoryMap;
+
+use crate::error::Result;
+use crate::rawdevice::drm_mode_create_dumb;
+use crate::rawdevice::drm_mode_crtc;
+use crate::rawdevice::drm_mode_get_connector;
+use crate::rawdevice::drm_mode_get_encoder;
+use crate::rawdevice::drm_mode_get_plane;
+use crate::rawdevice::drm_mode_get_property;
+use crate::rawdevice::drm_mode_modeinfo;
+use crate::rawdevice::drm_mode_property_enum;
+use crate::rawdevice::drm_version;
+use crate::trace::Trace;
+
+// Everything a Device needs from the DRM driver. RawDevice talks to the
+// kernel, other implementations can stand in for it when there's no
+// hardware around.
+pub(crate) trait Backend: Debug + Send + Sync {
+    fn as_fd(&self) -> BorrowedFd<'_>;
+
+    fn allocate_dumb_buffer(
+        &self,
+        width: usize,
+        height: usize,
+        bpp: usize,
+    ) -> Result<drm_mode_create_dumb>;
+
+    fn add_framebuffer(
+        &self,
+        handle: u32,
+        width: u32,
+        pitch: u32,
+        height: u32,
+        fmt: u32,
+    ) -> Result<u32>;
+
+    fn atomic_commit(
+        &self,
+        objs_ptr: Vec<u32>,
+        count_props_ptr: Vec<u32>,
+        props_ptr: Vec<u32>,
+        prop_values_ptr: Vec<u64>,
+        flags: u32,
+        user_data: u64,
+    ) -> Result<()>;
+
+    fn auth_magic(&self, magic: u32) -> Result<()>;
+
+    fn create_lease(
+        &self,
+        objects: &[u32],
+        flags: libc::c_int,
+    ) -> Result<(u32, OwnedFd)>;
+
+    fn create_property_blob(&self, data: &[u8]) -> Result<u32>;
+
+    fn destroy_property_blob(&self, id: u32) -> Result<()>;
+
+    fn remove_framebuffer(&self, id: u32);
+
+    fn drop_master(&self) -> Result<()>;
+
+    fn destroy_dumb_buffer(&self, handle: u32);
+
+    fn get_capability(&self, cap: u64) -> Result<u64>;
+
+    fn get_driver_version(
+        &self,
+        name: &mut Vec<u8>,
+        date: &mut Vec<u8>,
+        desc: &mut Vec<u8>,
+    ) -> Result<drm_version>;
+
+    fn get_encoder(&self, id: u32) -> Result<drm_mode_get_encoder>;
+
+    fn get_connector(
+        &self,
+        id: u32,
+        modes: Option<&mut Vec<drm_mode_modeinfo>>,
+        encoders: Option<&mut Vec<u32>>,
+        properties: Option<&mut Vec<u32>>,
+    ) -> Result<drm_mode_get_connector>;
+
+    fn get_connector_modes(&self, id: u32) -> Result<Vec<drm_mode_modeinfo>> {
+        let mut mod_info = Vec::new();
+
+        let _ = self.get_connector(id, Some(&mut mod_info), None, None)?;
+
+        Ok(mod_info)
+    }
+
+    fn get_connector_encoders(&self, id: u32) -> Result<Vec<u32>> {
+        let mut enc_ids = Vec::new();
+
+        let _ = self.get_connector(id, None, Some(&mut enc_ids), None)?;
+
+        Ok(enc_ids)
+    }
+
+    fn get_connectors(&self) -> Result<Vec<drm_mode_get_connector>>;
+
+    fn get_crtcs(&self) -> Result<Vec<drm_mode_crtc>>;
+
+    fn get_plane(&self, id: u32) -> Result<drm_mode_get_plane>;
+
+    fn get_planes(&self) -> Result<Vec<drm_mode_get_plane>>;
+
+    fn get_lease(&self) -> Result<Vec<u32>>;
+
+    fn get_magic(&self) -> Result<u32>;
+
+    fn get_property(
+        &self,
+        id: u32,
+        values: Option<&mut Vec<u64>>,
+        enums: Option<&mut Vec<drm_mode_property_enum>>,
+    ) -> Result<drm_mode_get_property>;
+
+    fn get_properties(
+        &self,
+        object_type: u32,
+        object_id: u32,
+    ) -> Result<Vec<(u32, u64)>>;
+
+    fn get_property_blob(&self, id: u32) -> Result<Vec<u8>>;
+
+    fn is_master(&self) -> Result<bool>;
+
+    fn list_lessees(&self) -> Result<Vec<u32>>;
+
+    fn map_dumb_buffer(&self, handle: u32, size: usize) -> Result<MemoryMap>;
+
+    fn read_events(&self, buf: &mut [u8]) -> Result<usize>;
+
+    fn revoke_lease(&self, lessee_id: u32) -> Result<()>;
+
+    fn set_crtc(
+        &self,
+        fb_id: u32,
+        crtc_id: u32,
+        connectors: &[u32],
+        x: u32,
+        y: u32,
+        mode: Option<&drm_mode_modeinfo>,
+    ) -> Result<()>;
+
+    fn set_client_capability(&self, cap: u64) -> Result<()>;
+
+    fn set_master(&self) -> Result<()>;
+
+    fn set_plane(
+        &self,
+        fb_id: u32,
+        plane_id: u32,
+        crtc_id: u32,
+        w: u32,
+        h: u32,
+    ) -> Result<()>;
+
+    fn take_trace(&self) -> Option<Trace> {
+        None
+    }
+}
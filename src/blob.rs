@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use crate::device::Device;
+use crate::error::Result;
+use crate::mode::Mode;
+use crate::object::Object;
+use crate::object::ObjectType;
+
+// Modes are shared by all the pipelines using them, and only destroyed
+// once the last one is done with it.
+#[derive(Debug)]
+#[derive(Default)]
+pub(crate) struct ModeBlobCache {
+    blobs: HashMap<Vec<u8>, (u32, usize)>,
+}
+
+impl ModeBlobCache {
+    pub(crate) fn acquire(&mut self, data: &[u8]) -> Option<u32> {
+        let (id, count) = self.blobs.get_mut(data)?;
+
+        *count += 1;
+        Some(*id)
+    }
+
+    pub(crate) fn insert(&mut self, data: Vec<u8>, id: u32) {
+        self.blobs.insert(data, (id, 1));
+    }
+
+    // Returns whether the blob isn't used anymore and can be destroyed
+    pub(crate) fn release(&mut self, id: u32) -> bool {
+        let entry = self.blobs.iter_mut().find(|(_, (blob, _))| *blob == id);
+
+        let key = match entry {
+            Some((_, (_, count))) if *count > 1 => {
+                *count -= 1;
+                return false;
+            },
+            Some((key, _)) => key.clone(),
+            None => return false,
+        };
+
+        self.blobs.remove(&key);
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct PropertyBlob {
+    dev:    Device,
+    id:     u32,
+    shared: bool,
+}
+
+impl Object for PropertyBlob {
+    fn get_dev(&self) -> &Device {
+        &self.dev
+    }
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    fn get_type(&self) -> ObjectType {
+        ObjectType::Blob
+    }
+}
+
+impl PropertyBlob {
+    pub fn new(dev: &Device, data: &[u8]) -> Result<PropertyBlob> {
+        let id = dev.raw().create_property_blob(data)?;
+
+        Ok(PropertyBlob {
+            dev: dev.clone(),
+            id,
+            shared: false,
+        })
+    }
+
+    // Each entry is the red, green and blue components of a
+    // struct drm_color_lut, for GAMMA_LUT and DEGAMMA_LUT.
+    pub fn from_color_lut(
+        dev: &Device,
+        lut: &[(u16, u16, u16)],
+    ) -> Result<PropertyBlob> {
+        let mut data = Vec::with_capacity(lut.len() * 8);
+
+        for (red, green, blue) in lut {
+            data.extend_from_slice(&red.to_ne_bytes());
+            data.extend_from_slice(&green.to_ne_bytes());
+            data.extend_from_slice(&blue.to_ne_bytes());
+            data.extend_from_slice(&0u16.to_ne_bytes());
+        }
+
+        PropertyBlob::new(dev, &data)
+    }
+
+    pub fn from_mode(dev: &Device, mode: &Mode) -> Result<PropertyBlob> {
+        let id = dev.acquire_mode_blob(mode)?;
+
+        Ok(PropertyBlob {
+            dev: dev.clone(),
+            id,
+            shared: true,
+        })
+    }
+
+    pub fn get_data(&self) -> Result<Vec<u8>> {
+        self.dev.get_blob(self.id)
+    }
+}
+
+impl Drop for PropertyBlob {
+    fn drop(&mut self) {
+        let _ = if self.shared {
+            self.dev.release_mode_blob(self.id)
+        } else {
+            self.dev.raw().destroy_property_blob(self.id)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulated::fixtures;
+    use crate::Object;
+    use crate::PropertyValue;
+
+    use super::PropertyBlob;
+
+    #[test]
+    fn test_property_blob() {
+        let dev = fixtures::hdmi_device();
+
+        let blob = PropertyBlob::from_color_lut(&dev, &[(1, 2, 3)]).unwrap();
+        let id = blob.get_id();
+        assert_eq!(blob.get_data().unwrap(), vec![1, 0, 2, 0, 3, 0, 0, 0]);
+
+        drop(blob);
+        assert!(dev.get_blob(id).is_err());
+
+        let connector = dev.get_connectors().unwrap().remove(0);
+        let mode = connector.get_modes().unwrap().remove(0);
+        let expected = PropertyBlob::from_mode(&dev, &mode)
+            .unwrap()
+            .get_data()
+            .unwrap();
+
+        let buffer = fixtures::framebuffer(&dev);
+        let pipeline = fixtures::pipeline_init(&dev, &buffer).build().unwrap();
+
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+        let mode_id = match crtc.get_property_value("MODE_ID").unwrap() {
+            PropertyValue::Blob(id) => id,
+            value => panic!("Unexpected property value {:?}", value),
+        };
+        assert_eq!(dev.get_blob(mode_id).unwrap(), expected);
+
+        // The CRTC still holds a reference to the mode
+        drop(pipeline);
+        assert_eq!(dev.get_blob(mode_id).unwrap(), expected);
+    }
+}
@@ -13,8 +13,8 @@ pub enum BufferType {
     Dumb,
 }
 
-pub struct Buffer<'a> {
-    dev:     &'a Device,
+pub struct Buffer {
+    dev:     Device,
     width:   usize,
     height:  usize,
     pitch:   usize,
@@ -24,13 +24,19 @@ pub struct Buffer<'a> {
     fb_id:   Option<u32>,
 }
 
-impl<'a> Buffer<'a> {
+// The mapping is only ever accessed through the buffer owning it, so it can
+// be handed over to another thread. Its content can only be changed through
+// a mutable reference, so the buffer can be shared as well.
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
     pub(crate) fn new(
         dev: &Device,
         dumb: drm_mode_create_dumb,
-    ) -> Result<Buffer<'_>> {
+    ) -> Result<Buffer> {
         Ok(Buffer {
-            dev,
+            dev: dev.clone(),
 
             width: dumb.width.try_into()?,
             height: dumb.height.try_into()?,
@@ -48,7 +54,7 @@ impl<'a> Buffer<'a> {
         self.fb_id
     }
 
-    pub fn get_data(&self) -> Option<&mut [u8]> {
+    pub fn get_data(&mut self) -> Option<&mut [u8]> {
         match self.mapping.as_ref() {
             Some(m) => {
                 let slice = unsafe {
@@ -73,8 +79,8 @@ impl<'a> Buffer<'a> {
         self.width
     }
 
-    pub fn into_framebuffer(mut self, fmt: Format) -> Result<Buffer<'a>> {
-        let fb_id = self.dev.raw.add_framebuffer(
+    pub fn into_framebuffer(mut self, fmt: Format) -> Result<Buffer> {
+        let fb_id = self.dev.raw().add_framebuffer(
             self.handle,
             self.width.try_into()?,
             self.pitch.try_into()?,
@@ -87,8 +93,8 @@ impl<'a> Buffer<'a> {
         Ok(self)
     }
 
-    pub fn map(mut self) -> Result<Buffer<'a>> {
-        let map = self.dev.raw.map_dumb_buffer(self.handle, self.size)?;
+    pub fn map(mut self) -> Result<Buffer> {
+        let map = self.dev.raw().map_dumb_buffer(self.handle, self.size)?;
 
         self.mapping = Some(map);
 
@@ -96,21 +102,21 @@ impl<'a> Buffer<'a> {
     }
 }
 
-impl<'a> Drop for Buffer<'a> {
+impl Drop for Buffer {
     fn drop(&mut self) {
         self.mapping = None;
 
         if self.fb_id.is_some() {
             let fb_id = self.fb_id.unwrap();
 
-            self.dev.raw.remove_framebuffer(fb_id);
+            self.dev.raw().remove_framebuffer(fb_id);
         }
 
-        self.dev.raw.destroy_dumb_buffer(self.handle);
+        self.dev.raw().destroy_dumb_buffer(self.handle);
     }
 }
 
-impl fmt::Debug for Buffer<'_> {
+impl fmt::Debug for Buffer {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_> ) -> fmt::Result {
         fmt.debug_struct("Buffer")
             .field("width", &self.width)
@@ -1,8 +1,5 @@
-use std::convert::TryFrom;
 use std::fmt;
 
-use num_enum::TryFromPrimitive;
-
 use crate::device::Device;
 use crate::encoder::Encoder;
 use crate::error::Result;
@@ -16,12 +13,33 @@ use crate::rawdevice::drm_mode_get_connector;
 #[derive(Copy)]
 #[derive(Debug)]
 #[derive(PartialEq)]
-#[derive(TryFromPrimitive)]
-#[repr(u32)]
 pub enum ConnectorStatus {
-    Connected = 1,
+    Connected,
     Disconnected,
     Unknown,
+    Other(u32),
+}
+
+impl From<u32> for ConnectorStatus {
+    fn from(status: u32) -> Self {
+        match status {
+            1 => ConnectorStatus::Connected,
+            2 => ConnectorStatus::Disconnected,
+            3 => ConnectorStatus::Unknown,
+            status => ConnectorStatus::Other(status),
+        }
+    }
+}
+
+impl From<ConnectorStatus> for u32 {
+    fn from(status: ConnectorStatus) -> Self {
+        match status {
+            ConnectorStatus::Connected => 1,
+            ConnectorStatus::Disconnected => 2,
+            ConnectorStatus::Unknown => 3,
+            ConnectorStatus::Other(status) => status,
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -29,8 +47,6 @@ pub enum ConnectorStatus {
 #[derive(Copy)]
 #[derive(Debug)]
 #[derive(PartialEq)]
-#[derive(TryFromPrimitive)]
-#[repr(u32)]
 pub enum ConnectorType {
     Unknown,
     VGA,
@@ -52,6 +68,66 @@ pub enum ConnectorType {
     DPI,
     Writeback,
     SPI,
+    USB,
+    Other(u32),
+}
+
+impl From<u32> for ConnectorType {
+    fn from(type_: u32) -> Self {
+        match type_ {
+            0 => ConnectorType::Unknown,
+            1 => ConnectorType::VGA,
+            2 => ConnectorType::DVII,
+            3 => ConnectorType::DVID,
+            4 => ConnectorType::DVIA,
+            5 => ConnectorType::Composite,
+            6 => ConnectorType::SVIDEO,
+            7 => ConnectorType::LVDS,
+            8 => ConnectorType::Component,
+            9 => ConnectorType::MiniDin9,
+            10 => ConnectorType::DisplayPort,
+            11 => ConnectorType::HDMIA,
+            12 => ConnectorType::HDMIB,
+            13 => ConnectorType::TV,
+            14 => ConnectorType::EDP,
+            15 => ConnectorType::Virtual,
+            16 => ConnectorType::DSI,
+            17 => ConnectorType::DPI,
+            18 => ConnectorType::Writeback,
+            19 => ConnectorType::SPI,
+            20 => ConnectorType::USB,
+            type_ => ConnectorType::Other(type_),
+        }
+    }
+}
+
+impl From<ConnectorType> for u32 {
+    fn from(type_: ConnectorType) -> Self {
+        match type_ {
+            ConnectorType::Unknown => 0,
+            ConnectorType::VGA => 1,
+            ConnectorType::DVII => 2,
+            ConnectorType::DVID => 3,
+            ConnectorType::DVIA => 4,
+            ConnectorType::Composite => 5,
+            ConnectorType::SVIDEO => 6,
+            ConnectorType::LVDS => 7,
+            ConnectorType::Component => 8,
+            ConnectorType::MiniDin9 => 9,
+            ConnectorType::DisplayPort => 10,
+            ConnectorType::HDMIA => 11,
+            ConnectorType::HDMIB => 12,
+            ConnectorType::TV => 13,
+            ConnectorType::EDP => 14,
+            ConnectorType::Virtual => 15,
+            ConnectorType::DSI => 16,
+            ConnectorType::DPI => 17,
+            ConnectorType::Writeback => 18,
+            ConnectorType::SPI => 19,
+            ConnectorType::USB => 20,
+            ConnectorType::Other(type_) => type_,
+        }
+    }
 }
 
 impl fmt::Display for ConnectorType {
@@ -73,7 +149,10 @@ impl fmt::Display for ConnectorType {
             ConnectorType::SPI => write!(f, "SPI"),
             ConnectorType::SVIDEO => write!(f, "S-VIDEO"),
             ConnectorType::TV => write!(f, "TV"),
-            ConnectorType::Unknown => write!(f, "Unknown"),
+            ConnectorType::USB => write!(f, "USB"),
+            ConnectorType::Unknown | ConnectorType::Other(_) => {
+                write!(f, "Unknown")
+            },
             ConnectorType::VGA => write!(f, "VGA"),
             ConnectorType::Virtual => write!(f, "Virtual"),
             ConnectorType::Writeback => write!(f, "Writeback"),
@@ -82,8 +161,8 @@ impl fmt::Display for ConnectorType {
 }
 
 #[derive(Debug)]
-pub struct Connector<'a> {
-    dev:       &'a Device,
+pub struct Connector {
+    dev:       Device,
     id:        u32,
     type_:     ConnectorType,
     type_id:   u32,
@@ -92,9 +171,9 @@ pub struct Connector<'a> {
     mm_width:  usize,
 }
 
-impl<'a> Object for Connector<'_> {
+impl Object for Connector {
     fn get_dev(&self) -> &Device {
-        self.dev
+        &self.dev
     }
 
     fn get_id(&self) -> u32 {
@@ -106,16 +185,16 @@ impl<'a> Object for Connector<'_> {
     }
 }
 
-impl<'a> Connector<'a> {
+impl Connector {
     pub(crate) fn new(
-        dev: &'a Device,
+        dev: &Device,
         connector: drm_mode_get_connector,
-    ) -> Result<Connector<'_>> {
+    ) -> Result<Connector> {
         Ok(Connector {
-            dev,
+            dev: dev.clone(),
             id: connector.connector_id,
-            status: ConnectorStatus::try_from(connector.connection).unwrap(),
-            type_: ConnectorType::try_from(connector.connector_type).unwrap(),
+            status: ConnectorStatus::from(connector.connection),
+            type_: ConnectorType::from(connector.connector_type),
             // For some reason the type ID starts at 1, make it consistent
             type_id: connector.connector_type_id - 1,
             mm_height: connector.mm_height as usize,
@@ -123,7 +202,7 @@ impl<'a> Connector<'a> {
         })
     }
 
-    pub fn get_encoders(&'_ self) -> Result<Vec<Encoder<'a>>> {
+    pub fn get_encoders(&self) -> Result<Vec<Encoder>> {
         self.dev.get_connector_encoders(self)
     }
 
@@ -131,7 +210,7 @@ impl<'a> Connector<'a> {
         self.type_id
     }
 
-    pub fn get_modes(&'_ self) -> Result<Vec<Mode>> {
+    pub fn get_modes(&self) -> Result<Vec<Mode>> {
         self.dev.get_connector_modes(self)
     }
 
@@ -143,3 +222,52 @@ impl<'a> Connector<'a> {
         self.type_
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::SimulatedDevice;
+
+    use super::ConnectorStatus;
+    use super::ConnectorType;
+
+    #[test]
+    fn test_connector_enums() {
+        assert_eq!(ConnectorType::from(11), ConnectorType::HDMIA);
+        assert_eq!(ConnectorType::from(20), ConnectorType::USB);
+        assert_eq!(ConnectorType::from(42), ConnectorType::Other(42));
+        assert_eq!(u32::from(ConnectorType::USB), 20);
+        assert_eq!(u32::from(ConnectorType::Other(42)), 42);
+        assert_eq!(ConnectorType::Other(42).to_string(), "Unknown");
+
+        assert_eq!(ConnectorStatus::from(1), ConnectorStatus::Connected);
+        assert_eq!(ConnectorStatus::from(0), ConnectorStatus::Other(0));
+        assert_eq!(u32::from(ConnectorStatus::Unknown), 3);
+    }
+
+    #[test]
+    fn test_connector_unknown_type() {
+        let dev = SimulatedDevice::new()
+            .add_output(
+                ConnectorType::USB,
+                ConnectorStatus::Connected,
+                1280,
+                720,
+                60,
+            )
+            .add_output(
+                ConnectorType::Other(64),
+                ConnectorStatus::Other(4),
+                1280,
+                720,
+                60,
+            )
+            .into_device()
+            .unwrap();
+
+        let connectors = dev.get_connectors().unwrap();
+        assert_eq!(connectors[0].get_type(), ConnectorType::USB);
+        assert_eq!(connectors[1].get_type(), ConnectorType::Other(64));
+        assert_eq!(connectors[1].get_status(), ConnectorStatus::Other(4));
+        assert_eq!(connectors[1].get_encoders().unwrap().len(), 1);
+    }
+}
@@ -6,14 +6,14 @@ use crate::plane::Plane;
 use crate::rawdevice::drm_mode_crtc;
 
 #[derive(Debug)]
-pub struct Crtc<'a> {
-    dev: &'a Device,
+pub struct Crtc {
+    dev: Device,
     id:  u32,
 }
 
-impl<'a> Object for Crtc<'a> {
+impl Object for Crtc {
     fn get_dev(&self) -> &Device {
-        self.dev
+        &self.dev
     }
 
     fn get_id(&self) -> u32 {
@@ -25,15 +25,15 @@ impl<'a> Object for Crtc<'a> {
     }
 }
 
-impl<'a> Crtc<'a> {
-    pub(crate) fn new(dev: &'_ Device, crtc: drm_mode_crtc) -> Result<Crtc<'_>> {
+impl Crtc {
+    pub(crate) fn new(dev: &Device, crtc: drm_mode_crtc) -> Result<Crtc> {
         Ok(Crtc {
-            dev,
+            dev: dev.clone(),
             id: crtc.crtc_id,
         })
     }
 
-    pub fn get_possible_planes(&'_ self) -> Result<Vec<Plane<'a>>> {
+    pub fn get_possible_planes(&self) -> Result<Vec<Plane>> {
         let planes = self.dev.get_planes()?;
 
         let mut ret = Vec::with_capacity(planes.len());
@@ -1,21 +1,49 @@
 use std::convert::TryInto;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::OwnedFd;
+use std::os::unix::io::RawFd;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use num_enum::TryFromPrimitive;
 
 use crate::atomic::AtomicProperty;
+use crate::atomic::AtomicRequest;
+use crate::atomic::CommitOptions;
+use crate::backend::Backend;
+use crate::blob::ModeBlobCache;
+use crate::blob::PropertyBlob;
 use crate::buffer::Buffer;
 use crate::buffer::BufferType;
 use crate::connector::Connector;
 use crate::crtc::Crtc;
+use crate::driver::DriverInfo;
 use crate::encoder::Encoder;
 use crate::error::Error;
 use crate::error::Result;
+use crate::event::Event;
+use crate::event::EventReader;
+use crate::lease::Lease;
 use crate::mode::Mode;
 use crate::object::Object;
 use crate::plane::Plane;
 use crate::property::Property;
+use crate::property::PropertyCache;
+use crate::property::PropertyValue;
+use crate::property::RawProperty;
+use crate::rawdevice::drm_mode_modeinfo;
 use crate::rawdevice::RawDevice;
+use crate::trace::Trace;
+use crate::trace::TracingBackend;
 
+#[derive(Clone)]
+#[derive(Copy)]
 #[derive(Debug)]
-#[allow(dead_code)]
+#[derive(PartialEq)]
+#[derive(TryFromPrimitive)]
 #[repr(u64)]
 pub enum ClientCapability {
     Stereo3d = 1,
@@ -25,20 +53,291 @@ pub enum ClientCapability {
     WritebackConnectors,
 }
 
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[repr(u64)]
+pub enum DeviceCapability {
+    DumbBuffer          = 0x01,
+    DumbPreferredDepth  = 0x03,
+    DumbPreferShadow    = 0x04,
+    Prime               = 0x05,
+    TimestampMonotonic  = 0x06,
+    AsyncPageFlip       = 0x07,
+    CursorWidth         = 0x08,
+    CursorHeight        = 0x09,
+    AddFb2Modifiers     = 0x10,
+    PageFlipTarget      = 0x11,
+    CrtcInVblankEvent   = 0x12,
+    Syncobj             = 0x13,
+    AtomicAsyncPageFlip = 0x15,
+}
+
+const DRM_PRIME_CAP_IMPORT: u64 = 0x1;
+const DRM_PRIME_CAP_EXPORT: u64 = 0x2;
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Capability {
+    DumbBuffer(bool),
+    DumbPreferredDepth(u32),
+    DumbPreferShadow(bool),
+    Prime {
+        import: bool,
+        export: bool,
+    },
+    TimestampMonotonic(bool),
+    AsyncPageFlip(bool),
+    CursorWidth(u32),
+    CursorHeight(u32),
+    AddFb2Modifiers(bool),
+    PageFlipTarget(bool),
+    CrtcInVblankEvent(bool),
+    Syncobj(bool),
+    AtomicAsyncPageFlip(bool),
+}
+
+impl Capability {
+    fn new(cap: DeviceCapability, value: u64) -> Result<Self> {
+        let enabled = value != 0;
+
+        Ok(match cap {
+            DeviceCapability::DumbBuffer => Capability::DumbBuffer(enabled),
+            DeviceCapability::DumbPreferredDepth => {
+                Capability::DumbPreferredDepth(value.try_into()?)
+            },
+            DeviceCapability::DumbPreferShadow => {
+                Capability::DumbPreferShadow(enabled)
+            },
+            DeviceCapability::Prime => Capability::Prime {
+                import: (value & DRM_PRIME_CAP_IMPORT) != 0,
+                export: (value & DRM_PRIME_CAP_EXPORT) != 0,
+            },
+            DeviceCapability::TimestampMonotonic => {
+                Capability::TimestampMonotonic(enabled)
+            },
+            DeviceCapability::AsyncPageFlip => {
+                Capability::AsyncPageFlip(enabled)
+            },
+            DeviceCapability::CursorWidth => {
+                Capability::CursorWidth(value.try_into()?)
+            },
+            DeviceCapability::CursorHeight => {
+                Capability::CursorHeight(value.try_into()?)
+            },
+            DeviceCapability::AddFb2Modifiers => {
+                Capability::AddFb2Modifiers(enabled)
+            },
+            DeviceCapability::PageFlipTarget => {
+                Capability::PageFlipTarget(enabled)
+            },
+            DeviceCapability::CrtcInVblankEvent => {
+                Capability::CrtcInVblankEvent(enabled)
+            },
+            DeviceCapability::Syncobj => Capability::Syncobj(enabled),
+            DeviceCapability::AtomicAsyncPageFlip => {
+                Capability::AtomicAsyncPageFlip(enabled)
+            },
+        })
+    }
+
+    pub fn is_supported(&self) -> bool {
+        match *self {
+            Capability::DumbBuffer(v) |
+            Capability::DumbPreferShadow(v) |
+            Capability::TimestampMonotonic(v) |
+            Capability::AsyncPageFlip(v) |
+            Capability::AddFb2Modifiers(v) |
+            Capability::PageFlipTarget(v) |
+            Capability::CrtcInVblankEvent(v) |
+            Capability::Syncobj(v) |
+            Capability::AtomicAsyncPageFlip(v) => v,
+            Capability::DumbPreferredDepth(v) |
+            Capability::CursorWidth(v) |
+            Capability::CursorHeight(v) => v != 0,
+            Capability::Prime {
+                import,
+                export,
+            } => import || export,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DeviceBuilder {
+    capabilities: Vec<(ClientCapability, bool)>,
+    read_only:    bool,
+    cloexec:      bool,
+    nonblocking:  Option<bool>,
+    tracing:      bool,
+}
+
+impl Default for DeviceBuilder {
+    fn default() -> Self {
+        DeviceBuilder::new()
+    }
+}
+
+impl DeviceBuilder {
+    pub fn new() -> Self {
+        DeviceBuilder {
+            capabilities: Vec::new(),
+            read_only:    false,
+            cloexec:      true,
+            nonblocking:  None,
+            tracing:      false,
+        }
+    }
+
+    pub fn from_fd(self, fd: OwnedFd) -> Result<Device> {
+        let raw = RawDevice::from_fd(fd);
+
+        // The access mode of an open file can't be changed
+        if self.read_only && raw.is_writable()? {
+            return Err(Error::UnsupportedError);
+        }
+
+        // O_NONBLOCK is shared with whoever handed us the file, so we leave
+        // it alone unless asked to.
+        if let Some(nonblocking) = self.nonblocking {
+            raw.set_nonblocking(nonblocking)?;
+        }
+
+        raw.set_cloexec(self.cloexec)?;
+        self.build(Box::new(raw))
+    }
+
+    pub fn open(self, path: &str) -> Result<Device> {
+        let mut flags = 0;
+        if self.nonblocking == Some(true) {
+            flags |= libc::O_NONBLOCK;
+        }
+
+        let raw = RawDevice::new(path, !self.read_only, flags)?;
+
+        // The standard library always opens files with O_CLOEXEC
+        if !self.cloexec {
+            raw.set_cloexec(false)?;
+        }
+
+        self.build(Box::new(raw))
+    }
+
+    pub(crate) fn build(self, raw: Box<dyn Backend>) -> Result<Device> {
+        let raw: Box<dyn Backend> = if self.tracing {
+            Box::new(TracingBackend::new(raw))
+        } else {
+            raw
+        };
+
+        let mut client_caps = Vec::new();
+        for (cap, required) in self.capabilities {
+            match raw.set_client_capability(cap as u64) {
+                Ok(_) => client_caps.push(cap),
+                Err(err) if required => return Err(err),
+                Err(_) => continue,
+            };
+        }
+
+        let inner = DeviceInner {
+            raw,
+            client_caps: Mutex::new(client_caps),
+            events: Mutex::new(EventReader::default()),
+            properties: Mutex::new(PropertyCache::default()),
+            mode_blobs: Mutex::new(ModeBlobCache::default()),
+        };
+
+        Ok(Device {
+            inner: Arc::new(inner),
+        })
+    }
+
+    pub fn request_client_capability(
+        mut self,
+        cap: ClientCapability,
+    ) -> DeviceBuilder {
+        self.capabilities.push((cap, false));
+        self
+    }
+
+    pub fn require_client_capability(
+        mut self,
+        cap: ClientCapability,
+    ) -> DeviceBuilder {
+        self.capabilities.push((cap, true));
+        self
+    }
+
+    pub fn set_cloexec(mut self, cloexec: bool) -> DeviceBuilder {
+        self.cloexec = cloexec;
+        self
+    }
+
+    pub fn set_nonblocking(mut self, nonblocking: bool) -> DeviceBuilder {
+        self.nonblocking = Some(nonblocking);
+        self
+    }
+
+    pub fn set_read_only(mut self, read_only: bool) -> DeviceBuilder {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn set_tracing(mut self, tracing: bool) -> DeviceBuilder {
+        self.tracing = tracing;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct DeviceInner {
+    raw:         Box<dyn Backend>,
+    client_caps: Mutex<Vec<ClientCapability>>,
+    events:      Mutex<EventReader>,
+    properties:  Mutex<PropertyCache>,
+    mode_blobs:  Mutex<ModeBlobCache>,
+}
+
+// Cloning a Device only gives another handle to the same file descriptor,
+// which is closed once the device and all its objects are gone.
+#[derive(Clone)]
 #[derive(Debug)]
 pub struct Device {
-    pub(crate) raw: RawDevice,
+    inner: Arc<DeviceInner>,
+}
+
+impl AsFd for Device {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.raw().as_fd()
+    }
+}
+
+impl AsRawFd for Device {
+    fn as_raw_fd(&self) -> RawFd {
+        self.raw().as_fd().as_raw_fd()
+    }
 }
 
-impl<'a> Device {
+impl Device {
+    pub(crate) fn default_builder() -> DeviceBuilder {
+        DeviceBuilder::new()
+            .require_client_capability(ClientCapability::Atomic)
+            .require_client_capability(ClientCapability::UniversalPlanes)
+    }
+
     pub fn new(path: &str) -> Result<Device> {
-        let device = Device {
-            raw: RawDevice::new(path)?,
-        };
+        Device::default_builder().open(path)
+    }
+
+    pub fn from_fd(fd: OwnedFd) -> Result<Device> {
+        Device::default_builder().from_fd(fd)
+    }
 
-        Ok(device
-            .set_client_capability(ClientCapability::Atomic)?
-            .set_client_capability(ClientCapability::UniversalPlanes)?)
+    pub fn acquire_master(&self) -> Result<()> {
+        self.raw().set_master()
     }
 
     pub fn allocate_buffer(
@@ -47,66 +346,95 @@ impl<'a> Device {
         width: usize,
         height: usize,
         bpp: usize,
-    ) -> Result<Buffer<'_>> {
+    ) -> Result<Buffer> {
         let raw = match buftype {
             BufferType::Dumb => {
-                self.raw.allocate_dumb_buffer(width, height, bpp)?
+                self.raw().allocate_dumb_buffer(width, height, bpp)?
             },
         };
 
         Ok(Buffer::new(self, raw)?)
     }
 
-    pub(crate) fn atomic_commit(
+    pub(crate) fn acquire_mode_blob(&self, mode: &Mode) -> Result<u32> {
+        let data = mode.into_inner().as_bytes().to_vec();
+        let mut blobs = self.lock_mode_blobs();
+
+        if let Some(id) = blobs.acquire(&data) {
+            return Ok(id);
+        }
+
+        let id = self.raw().create_property_blob(&data)?;
+        blobs.insert(data, id);
+
+        Ok(id)
+    }
+
+    pub fn auth_magic(&self, magic: u32) -> Result<()> {
+        self.raw().auth_magic(magic)
+    }
+
+    pub fn commit(
         &self,
-        mut properties: Vec<AtomicProperty>,
+        request: &AtomicRequest,
+        options: CommitOptions,
     ) -> Result<()> {
-        let mut count_props = 0;
         let mut objs_ptr: Vec<u32> = Vec::new();
         let mut count_props_ptr: Vec<u32> = Vec::new();
         let mut props_ptr: Vec<u32> = Vec::new();
         let mut prop_values_ptr: Vec<u64> = Vec::new();
 
-        properties.sort();
-        properties.dedup();
-
-        let first_obj = properties[0].get_object_id();
-        let mut last_obj = first_obj;
+        // The kernel wants all the properties of an object next to each other
+        let mut properties: Vec<&AtomicProperty> = request.iter().collect();
+        properties.sort_by_key(|property| property.get_object_id());
 
-        objs_ptr.push(first_obj);
         for property in properties {
             let oid = property.get_object_id();
 
-            if oid != last_obj {
+            if objs_ptr.last() != Some(&oid) {
                 objs_ptr.push(oid);
-                count_props_ptr.push(count_props);
+                count_props_ptr.push(0);
+            }
 
-                last_obj = oid;
-                count_props = 0;
+            if let Some(count) = count_props_ptr.last_mut() {
+                *count += 1;
             }
 
-            count_props = count_props + 1;
             props_ptr.push(property.get_property_id());
             prop_values_ptr.push(property.get_value());
         }
-        count_props_ptr.push(count_props);
 
-        self.raw.atomic_commit(
+        self.raw().atomic_commit(
             objs_ptr,
             count_props_ptr,
             props_ptr,
             prop_values_ptr,
-        )?;
+            options.get_flags(),
+            options.get_user_data(),
+        )
+    }
 
-        Ok(())
+    pub fn create_lease(&self, objects: &[&dyn Object]) -> Result<Lease> {
+        let ids: Vec<u32> = objects.iter().map(|obj| obj.get_id()).collect();
+        let (lessee_id, fd) = self.raw().create_lease(&ids, libc::O_CLOEXEC)?;
+
+        Ok(Lease::new(lessee_id, fd))
+    }
+
+    pub fn drop_master(&self) -> Result<()> {
+        self.raw().drop_master()
+    }
+
+    pub fn get_blob(&self, id: u32) -> Result<Vec<u8>> {
+        self.raw().get_property_blob(id)
     }
 
     pub(crate) fn get_connector_encoders(
         &self,
-        connector: &Connector<'_>,
-    ) -> Result<Vec<Encoder<'_>>> {
+        connector: &Connector,
+    ) -> Result<Vec<Encoder>> {
         let id = connector.get_id();
-        let enc_ids = self.raw.get_connector_encoders(id)?;
+        let enc_ids = self.raw().get_connector_encoders(id)?;
 
         let mut encoders = Vec::with_capacity(enc_ids.len());
         for id in enc_ids {
@@ -118,10 +446,10 @@ impl<'a> Device {
 
     pub(crate) fn get_connector_modes(
         &self,
-        connector: &Connector<'_>,
+        connector: &Connector,
     ) -> Result<Vec<Mode>> {
         let id = connector.get_id();
-        let raw_modes = self.raw.get_connector_modes(id)?;
+        let raw_modes = self.raw().get_connector_modes(id)?;
 
         let mut modes = Vec::with_capacity(raw_modes.len());
         for mode in raw_modes {
@@ -131,8 +459,14 @@ impl<'a> Device {
         Ok(modes)
     }
 
-    pub fn get_connectors(&'a self) -> Result<Vec<Connector<'a>>> {
-        let raw_connectors = self.raw.get_connectors()?;
+    pub fn get_capability(&self, cap: DeviceCapability) -> Result<Capability> {
+        let value = self.raw().get_capability(cap as u64)?;
+
+        Capability::new(cap, value)
+    }
+
+    pub fn get_connectors(&self) -> Result<Vec<Connector>> {
+        let raw_connectors = self.raw().get_connectors()?;
 
         let mut connectors = Vec::with_capacity(raw_connectors.len());
         for connector in raw_connectors {
@@ -142,8 +476,20 @@ impl<'a> Device {
         Ok(connectors)
     }
 
-    pub fn get_crtcs(&'a self) -> Result<Vec<Crtc<'a>>> {
-        let raw_crtcs = self.raw.get_crtcs()?;
+    pub fn get_driver_info(&self) -> Result<DriverInfo> {
+        let mut name = Vec::new();
+        let mut date = Vec::new();
+        let mut desc = Vec::new();
+
+        let version =
+            self.raw()
+                .get_driver_version(&mut name, &mut date, &mut desc)?;
+
+        DriverInfo::new(version, &name, &date, &desc)
+    }
+
+    pub fn get_crtcs(&self) -> Result<Vec<Crtc>> {
+        let raw_crtcs = self.raw().get_crtcs()?;
 
         let mut crtcs = Vec::with_capacity(raw_crtcs.len());
         for crtc in raw_crtcs {
@@ -153,8 +499,16 @@ impl<'a> Device {
         Ok(crtcs)
     }
 
-    pub fn get_planes(&'a self) -> Result<Vec<Plane<'a>>> {
-        let raw_planes = self.raw.get_planes()?;
+    pub fn get_leased_objects(&self) -> Result<Vec<u32>> {
+        self.raw().get_lease()
+    }
+
+    pub fn get_magic(&self) -> Result<u32> {
+        self.raw().get_magic()
+    }
+
+    pub fn get_planes(&self) -> Result<Vec<Plane>> {
+        let raw_planes = self.raw().get_planes()?;
 
         let mut planes = Vec::with_capacity(raw_planes.len());
         for plane in raw_planes {
@@ -164,38 +518,177 @@ impl<'a> Device {
         Ok(planes)
     }
 
-    pub(crate) fn get_properties(
-        &'a self,
+    pub fn get_properties(
+        &self,
         obj: &impl Object,
-    ) -> Result<Vec<Property<'a>>> {
-        let prop_ids = self
-            .raw
-            .get_properties(obj.get_type() as u32, obj.get_id())?;
+    ) -> Result<Vec<Property>> {
+        let type_ = obj.get_type() as u32;
+        let id = obj.get_id();
+
+        let cached = self
+            .lock_properties()
+            .get_object(type_, id)
+            .map(|ids| ids.to_vec());
+
+        let prop_ids = match cached {
+            Some(ids) => ids,
+            None => {
+                let ids: Vec<u32> = self
+                    .raw()
+                    .get_properties(type_, id)?
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .collect();
+
+                self.lock_properties().insert_object(type_, id, &ids);
+                ids
+            },
+        };
 
         let mut properties = Vec::with_capacity(prop_ids.len());
         for id in prop_ids {
-            let property = self.raw.get_property(id)?;
-
-            properties.push(Property::new(self, property)?);
+            properties.push(self.get_property(id)?);
         }
 
         Ok(properties)
     }
 
+    pub(crate) fn get_property(&self, id: u32) -> Result<Property> {
+        let cached = self.lock_properties().get_property(id).cloned();
+
+        let raw = match cached {
+            Some(raw) => raw,
+            None => {
+                let mut values = Vec::new();
+                let mut enums = Vec::new();
+
+                let property = self.raw().get_property(
+                    id,
+                    Some(&mut values),
+                    Some(&mut enums),
+                )?;
+
+                let raw = RawProperty {
+                    property,
+                    values,
+                    enums,
+                };
+
+                self.lock_properties().insert_property(raw.clone());
+                raw
+            },
+        };
+
+        Property::new(self, &raw)
+    }
+
+    pub(crate) fn get_property_values(
+        &self,
+        obj: &impl Object,
+    ) -> Result<Vec<(Property, PropertyValue)>> {
+        let props = self
+            .raw()
+            .get_properties(obj.get_type() as u32, obj.get_id())?;
+
+        let mut values = Vec::with_capacity(props.len());
+        for (id, value) in props {
+            let property = self.get_property(id)?;
+            let value = property.decode(value);
+
+            values.push((property, value));
+        }
+
+        Ok(values)
+    }
+
+    pub fn has_client_capability(&self, cap: ClientCapability) -> bool {
+        self.lock_client_caps().contains(&cap)
+    }
+
+    // Objects can come and go on hotplug, with some of their ids being
+    // reused, so anyone listening for hotplug events should call this.
+    pub fn invalidate_property_cache(&self) {
+        self.lock_properties().clear();
+    }
+
+    pub fn is_master(&self) -> Result<bool> {
+        self.raw().is_master()
+    }
+
+    pub fn list_lessees(&self) -> Result<Vec<u32>> {
+        self.raw().list_lessees()
+    }
+
+    fn lock_client_caps(&self) -> MutexGuard<'_, Vec<ClientCapability>> {
+        match self.inner.client_caps.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn lock_mode_blobs(&self) -> MutexGuard<'_, ModeBlobCache> {
+        match self.inner.mode_blobs.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn lock_properties(&self) -> MutexGuard<'_, PropertyCache> {
+        match self.inner.properties.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub fn read_events(&self) -> Result<Vec<Event>> {
+        let mut buf = [0; 4096];
+        let len = self.raw().read_events(&mut buf)?;
+
+        let mut reader = match self.inner.events.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        reader.parse(&buf[..len])
+    }
+
+    pub(crate) fn raw(&self) -> &dyn Backend {
+        self.inner.raw.as_ref()
+    }
+
+    pub(crate) fn release_mode_blob(&self, id: u32) -> Result<()> {
+        if self.lock_mode_blobs().release(id) {
+            self.raw().destroy_property_blob(id)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn revoke_lease(&self, lessee_id: u32) -> Result<()> {
+        self.raw().revoke_lease(lessee_id)
+    }
+
     pub fn set_client_capability(
         self,
         cap: ClientCapability,
     ) -> Result<Device> {
-        self.raw.set_client_capability(cap as u64)?;
+        self.raw().set_client_capability(cap as u64)?;
+
+        // Some properties are only exposed to clients with a given cap
+        self.invalidate_property_cache();
+
+        if !self.has_client_capability(cap) {
+            self.lock_client_caps().push(cap);
+        }
 
         Ok(self)
     }
 
     pub fn set_crtc(
         &self,
-        buffer: &Buffer<'_>,
-        crtc: &Crtc<'_>,
-        connectors: &[&Connector<'_>],
+        buffer: &Buffer,
+        crtc: &Crtc,
+        connectors: &[&Connector],
         x: usize,
         y: usize,
         mode: Option<&Mode>,
@@ -210,7 +703,7 @@ impl<'a> Device {
             .get_framebuffer_id()
             .ok_or(Error::UninitializedError)?;
 
-        self.raw.set_crtc(
+        self.raw().set_crtc(
             fb_id,
             crtc.get_id(),
             &con_ids,
@@ -224,9 +717,9 @@ impl<'a> Device {
 
     pub fn set_plane(
         &self,
-        buffer: &Buffer<'_>,
-        plane: &Plane<'_>,
-        crtc: &Crtc<'_>,
+        buffer: &Buffer,
+        plane: &Plane,
+        crtc: &Crtc,
         width: usize,
         height: usize,
     ) -> Result<()> {
@@ -234,7 +727,7 @@ impl<'a> Device {
             .get_framebuffer_id()
             .ok_or(Error::UninitializedError)?;
 
-        self.raw.set_plane(
+        self.raw().set_plane(
             fb_id,
             plane.get_id(),
             crtc.get_id(),
@@ -244,4 +737,125 @@ impl<'a> Device {
 
         Ok(())
     }
+
+    fn snapshot_object(
+        &self,
+        mut request: AtomicRequest,
+        object: &impl Object,
+        mode: Option<&drm_mode_modeinfo>,
+    ) -> Result<AtomicRequest> {
+        let props = self
+            .raw()
+            .get_properties(object.get_type() as u32, object.get_id())?;
+
+        for (id, value) in props {
+            let property = self.get_property(id)?;
+            if property.is_immutable() {
+                continue;
+            }
+
+            let value = match (property.get_name(), mode) {
+                // The kernel only allows DPMS to be set through SETPROPERTY
+                ("DPMS", _) => continue,
+
+                // The current mode blob goes away as soon as another one
+                // replaces it, so we need a copy of our own to restore it.
+                ("MODE_ID", Some(mode)) if value != 0 => {
+                    let blob = PropertyBlob::new(self, mode.as_bytes())?;
+                    let id = blob.get_id();
+
+                    request.add_blob(blob);
+                    id as u64
+                },
+
+                _ => value,
+            };
+
+            request = request.add_property_id(object, id, value)?;
+        }
+
+        Ok(request)
+    }
+
+    pub fn snapshot_state(&self) -> Result<AtomicRequest> {
+        let mut request = AtomicRequest::new(self);
+
+        for connector in self.get_connectors()? {
+            request = self.snapshot_object(request, &connector, None)?;
+        }
+
+        for raw in self.raw().get_crtcs()? {
+            let mode = if raw.mode_valid != 0 {
+                Some(raw.mode)
+            } else {
+                None
+            };
+
+            let crtc = Crtc::new(self, raw)?;
+            request = self.snapshot_object(request, &crtc, mode.as_ref())?;
+        }
+
+        for plane in self.get_planes()? {
+            request = self.snapshot_object(request, &plane, None)?;
+        }
+
+        Ok(request)
+    }
+
+    pub fn take_trace(&self) -> Option<Trace> {
+        self.raw().take_trace()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::io::OwnedFd;
+
+    use crate::DeviceBuilder;
+    use crate::Error;
+
+    fn null_fd(write: bool, flags: i32) -> OwnedFd {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(write)
+            .custom_flags(flags)
+            .open("/dev/null")
+            .unwrap();
+
+        OwnedFd::from(file)
+    }
+
+    fn status_flags(fd: &impl AsRawFd) -> i32 {
+        unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFL) }
+    }
+
+    #[test]
+    fn test_device_builder_from_fd() {
+        let dev = DeviceBuilder::new()
+            .set_nonblocking(true)
+            .from_fd(null_fd(true, 0))
+            .unwrap();
+        assert_ne!(status_flags(&dev) & libc::O_NONBLOCK, 0);
+
+        // The file is shared with the sender, its flags are theirs
+        let dev = DeviceBuilder::new()
+            .from_fd(null_fd(true, libc::O_NONBLOCK))
+            .unwrap();
+        assert_ne!(status_flags(&dev) & libc::O_NONBLOCK, 0);
+
+        let err = DeviceBuilder::new()
+            .set_read_only(true)
+            .from_fd(null_fd(true, 0))
+            .unwrap_err();
+        assert_eq!(err, Error::UnsupportedError);
+
+        let dev = DeviceBuilder::new()
+            .set_read_only(true)
+            .from_fd(null_fd(false, 0))
+            .unwrap();
+        assert_eq!(status_flags(&dev) & libc::O_ACCMODE, libc::O_RDONLY);
+    }
 }
@@ -0,0 +1,61 @@
+use crate::error::Result;
+use crate::rawdevice::drm_version;
+
+fn bytes_to_string(bytes: &[u8]) -> Result<String> {
+    Ok(std::str::from_utf8(bytes)?
+        .trim_end_matches(char::from(0))
+        .to_string())
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct DriverInfo {
+    name:        String,
+    major:       i32,
+    minor:       i32,
+    patch:       i32,
+    date:        String,
+    description: String,
+}
+
+impl DriverInfo {
+    pub(crate) fn new(
+        version: drm_version,
+        name: &[u8],
+        date: &[u8],
+        desc: &[u8],
+    ) -> Result<Self> {
+        Ok(DriverInfo {
+            name:        bytes_to_string(name)?,
+            major:       version.version_major,
+            minor:       version.version_minor,
+            patch:       version.version_patchlevel,
+            date:        bytes_to_string(date)?,
+            description: bytes_to_string(desc)?,
+        })
+    }
+
+    pub fn get_date(&self) -> &str {
+        &self.date
+    }
+
+    pub fn get_description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn get_major(&self) -> i32 {
+        self.major
+    }
+
+    pub fn get_minor(&self) -> i32 {
+        self.minor
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_patch(&self) -> i32 {
+        self.patch
+    }
+}
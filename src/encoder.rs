@@ -1,14 +1,9 @@
-use std::convert::TryFrom;
-
-use num_enum::TryFromPrimitive;
-
 use crate::crtc::Crtc;
 use crate::device::Device;
 use crate::error::Result;
 
+#[allow(dead_code)]
 #[derive(Debug)]
-#[derive(TryFromPrimitive)]
-#[repr(u32)]
 pub enum EncoderType {
     None,
     DAC,
@@ -18,28 +13,47 @@ pub enum EncoderType {
     Virtual,
     DSI,
     DPMST,
+    DPI,
+    Other(u32),
+}
+
+impl From<u32> for EncoderType {
+    fn from(type_: u32) -> Self {
+        match type_ {
+            0 => EncoderType::None,
+            1 => EncoderType::DAC,
+            2 => EncoderType::TMDS,
+            3 => EncoderType::LVDS,
+            4 => EncoderType::TVDAC,
+            5 => EncoderType::Virtual,
+            6 => EncoderType::DSI,
+            7 => EncoderType::DPMST,
+            8 => EncoderType::DPI,
+            type_ => EncoderType::Other(type_),
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct Encoder<'a> {
-    dev:   &'a Device,
+pub struct Encoder {
+    dev:   Device,
     id:    u32,
     type_: EncoderType,
 }
 
-impl<'a> Encoder<'a> {
-    pub(crate) fn new_from_id(dev: &'a Device, id: u32) -> Result<Encoder<'a>> {
-        let encoder = dev.raw.get_encoder(id)?;
+impl Encoder {
+    pub(crate) fn new_from_id(dev: &Device, id: u32) -> Result<Encoder> {
+        let encoder = dev.raw().get_encoder(id)?;
 
         Ok(Encoder {
-            dev,
+            dev: dev.clone(),
             id,
-            type_: EncoderType::try_from(encoder.encoder_type).unwrap(),
+            type_: EncoderType::from(encoder.encoder_type),
         })
     }
 
-    pub fn get_possible_crtcs(&'_ self) -> Result<Vec<Crtc<'a>>> {
-        let encoder = self.dev.raw.get_encoder(self.id)?;
+    pub fn get_possible_crtcs(&self) -> Result<Vec<Crtc>> {
+        let encoder = self.dev.raw().get_encoder(self.id)?;
         let crtcs = self.dev.get_crtcs()?;
 
         let ret = crtcs
@@ -0,0 +1,283 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::device::Device;
+use crate::error::Error;
+use crate::error::Result;
+
+const DEFAULT_DEV_ROOT: &str = "/dev/dri";
+const DEFAULT_SYSFS_ROOT: &str = "/sys/class/drm";
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum DeviceNodeType {
+    Primary,
+    Control,
+    Render,
+}
+
+impl DeviceNodeType {
+    fn from_name(name: &str) -> Option<DeviceNodeType> {
+        let prefixes = [
+            ("card", DeviceNodeType::Primary),
+            ("controlD", DeviceNodeType::Control),
+            ("renderD", DeviceNodeType::Render),
+        ];
+
+        let (minor, node_type) = prefixes.iter().find_map(|(prefix, kind)| {
+            name.strip_prefix(prefix).map(|minor| (minor, *kind))
+        })?;
+
+        // Connectors also show up in the DRM class as card0-HDMI-A-1
+        if minor.is_empty() || !minor.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        Some(node_type)
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct DeviceInfo {
+    name:      String,
+    path:      PathBuf,
+    node_type: DeviceNodeType,
+    driver:    Option<String>,
+    subsystem: Option<String>,
+    bus_path:  Option<PathBuf>,
+    has_kms:   bool,
+}
+
+impl DeviceInfo {
+    fn new(sysfs_root: &Path, dev_root: &Path, name: &str) -> Option<Self> {
+        let node_type = DeviceNodeType::from_name(name)?;
+        let path = dev_root.join(name);
+        if !path.exists() {
+            return None;
+        }
+
+        let device = sysfs_root.join(name).join("device");
+        let link_name = |link: &str| {
+            fs::read_link(device.join(link))
+                .ok()?
+                .file_name()?
+                .to_str()
+                .map(|s| s.to_string())
+        };
+
+        let driver = link_name("driver");
+        let subsystem = link_name("subsystem");
+        let bus_path = fs::canonicalize(&device).ok();
+
+        // The KMS connectors are exposed as siblings named after the
+        // primary node, ie. card0-HDMI-A-1
+        let prefix = format!("{}-", name);
+        let is_connector = |entry: fs::DirEntry| {
+            entry.file_name().to_string_lossy().starts_with(&prefix)
+        };
+
+        let has_kms = node_type == DeviceNodeType::Primary &&
+            fs::read_dir(sysfs_root)
+                .map(|entries| {
+                    entries.filter_map(|entry| entry.ok()).any(is_connector)
+                })
+                .unwrap_or(false);
+
+        Some(DeviceInfo {
+            name: name.to_string(),
+            path,
+            node_type,
+            driver,
+            subsystem,
+            bus_path,
+            has_kms,
+        })
+    }
+
+    pub fn get_bus_path(&self) -> Option<&Path> {
+        self.bus_path.as_deref()
+    }
+
+    pub fn get_driver(&self) -> Option<&str> {
+        self.driver.as_deref()
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_node_type(&self) -> DeviceNodeType {
+        self.node_type
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_subsystem(&self) -> Option<&str> {
+        self.subsystem.as_deref()
+    }
+
+    pub fn has_kms(&self) -> bool {
+        self.has_kms
+    }
+
+    pub fn open(&self) -> Result<Device> {
+        let path = self.path.to_str().ok_or(Error::StringConversionError)?;
+
+        Device::new(path)
+    }
+}
+
+#[derive(Debug)]
+pub struct DeviceEnumerator {
+    dev_root:   PathBuf,
+    sysfs_root: PathBuf,
+}
+
+impl Default for DeviceEnumerator {
+    fn default() -> Self {
+        DeviceEnumerator::new()
+    }
+}
+
+impl DeviceEnumerator {
+    pub fn new() -> Self {
+        DeviceEnumerator {
+            dev_root:   PathBuf::from(DEFAULT_DEV_ROOT),
+            sysfs_root: PathBuf::from(DEFAULT_SYSFS_ROOT),
+        }
+    }
+
+    pub fn set_dev_root(mut self, root: impl AsRef<Path>) -> Self {
+        self.dev_root = root.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn set_sysfs_root(mut self, root: impl AsRef<Path>) -> Self {
+        self.sysfs_root = root.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn enumerate(&self) -> Result<Vec<DeviceInfo>> {
+        let mut devices = Vec::new();
+
+        for entry in fs::read_dir(&self.sysfs_root)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_str().ok_or(Error::StringConversionError)?;
+
+            if let Some(info) =
+                DeviceInfo::new(&self.sysfs_root, &self.dev_root, name)
+            {
+                devices.push(info);
+            }
+        }
+
+        devices.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(devices)
+    }
+
+    pub fn open_first_with_connectors(&self) -> Result<Device> {
+        let candidates = self
+            .enumerate()?
+            .into_iter()
+            .filter(|info| info.has_kms());
+
+        for info in candidates {
+            let device = match info.open() {
+                Ok(dev) => dev,
+                Err(_) => continue,
+            };
+
+            let has_connectors = device
+                .get_connectors()
+                .map(|connectors| !connectors.is_empty())
+                .unwrap_or(false);
+
+            if has_connectors {
+                return Ok(device);
+            }
+        }
+
+        Err(Error::NoneError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+    use std::path::PathBuf;
+
+    use super::DeviceEnumerator;
+    use super::DeviceNodeType;
+
+    fn fake_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir()
+            .join(format!("doremi-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let dev = root.join("dev/dri");
+        let class = root.join("sys/class/drm");
+        let platform = root.join("sys/devices/platform/soc/gpu");
+        let drivers = root.join("sys/bus/platform/drivers/vc4-drm");
+        let bus = root.join("sys/bus/platform");
+
+        fs::create_dir_all(&dev).unwrap();
+        fs::create_dir_all(&class).unwrap();
+        fs::create_dir_all(&platform).unwrap();
+        fs::create_dir_all(&drivers).unwrap();
+
+        symlink(&drivers, platform.join("driver")).unwrap();
+        symlink(&bus, platform.join("subsystem")).unwrap();
+
+        for node in &["card0", "card1", "renderD128"] {
+            fs::write(dev.join(node), b"").unwrap();
+            fs::create_dir_all(class.join(node)).unwrap();
+        }
+
+        symlink(&platform, class.join("card0/device")).unwrap();
+        symlink(&platform, class.join("renderD128/device")).unwrap();
+        fs::create_dir_all(class.join("card0-HDMI-A-1")).unwrap();
+        fs::write(class.join("version"), b"drm 1.1.0").unwrap();
+
+        root
+    }
+
+    #[test]
+    fn test_enumerate_fake_tree() {
+        let root = fake_tree("enumerate");
+        let devices = DeviceEnumerator::new()
+            .set_dev_root(root.join("dev/dri"))
+            .set_sysfs_root(root.join("sys/class/drm"))
+            .enumerate()
+            .unwrap();
+
+        let names: Vec<&str> =
+            devices.iter().map(|info| info.get_name()).collect();
+        assert_eq!(names, ["card0", "card1", "renderD128"]);
+
+        let card0 = &devices[0];
+        assert_eq!(card0.get_node_type(), DeviceNodeType::Primary);
+        assert_eq!(card0.get_driver(), Some("vc4-drm"));
+        assert_eq!(card0.get_subsystem(), Some("platform"));
+        assert!(card0.get_bus_path().unwrap().ends_with("platform/soc/gpu"));
+        assert!(card0.has_kms());
+
+        let card1 = &devices[1];
+        assert_eq!(card1.get_driver(), None);
+        assert!(!card1.has_kms());
+
+        let render = &devices[2];
+        assert_eq!(render.get_node_type(), DeviceNodeType::Render);
+        assert_eq!(render.get_driver(), Some("vc4-drm"));
+        assert!(!render.has_kms());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
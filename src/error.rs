@@ -1,24 +1,247 @@
+use std::fmt;
+
+use crate::object::Object;
+use crate::object::ObjectType;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct KernelError {
+    operation: String,
+    errno:     i32,
+    object_id: Option<u32>,
+}
+
+impl KernelError {
+    pub fn get_errno(&self) -> i32 {
+        self.errno
+    }
+
+    pub fn get_object_id(&self) -> Option<u32> {
+        self.object_id
+    }
+
+    pub fn get_operation(&self) -> &str {
+        &self.operation
+    }
+}
+
+impl fmt::Display for KernelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let err = std::io::Error::from_raw_os_error(self.errno);
+
+        match self.object_id {
+            Some(id) => {
+                write!(f, "{} on object {}: {}", self.operation, id, err)
+            },
+            None => write!(f, "{}: {}", self.operation, err),
+        }
+    }
+}
+
+#[derive(Clone)]
 #[derive(Debug)]
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Error {
-    IOError,
+    BusyError(KernelError),
+    DuplicatePropertyError {
+        object_id:   u32,
+        property_id: u32,
+    },
+    IOError(KernelError),
+    InvalidArgumentError(KernelError),
+    InvalidConfigurationError,
+    NoSpaceError(KernelError),
     NoneError,
+    NotMasterError(KernelError),
+    NotSupportedError(KernelError),
     OutOfRangeError,
+    PermissionDeniedError(KernelError),
+    PropertyNotFoundError {
+        object_id:   u32,
+        object_type: ObjectType,
+        property:    String,
+    },
+    PropertyNotQueuedError {
+        object_id:   u32,
+        property_id: u32,
+    },
     StringConversionError,
+    TraceDivergenceError {
+        ioctl: String,
+    },
     UnsupportedError,
     UninitializedError,
 }
 
+impl Error {
+    pub(crate) fn from_errno(
+        operation: &str,
+        errno: i32,
+        object_id: Option<u32>,
+    ) -> Error {
+        let err = KernelError {
+            operation: operation.to_string(),
+            errno,
+            object_id,
+        };
+
+        match errno {
+            libc::EACCES | libc::EPERM => Error::PermissionDeniedError(err),
+            libc::EBUSY => Error::BusyError(err),
+            libc::EINVAL | libc::ERANGE => Error::InvalidArgumentError(err),
+            libc::ENOMEM | libc::ENOSPC => Error::NoSpaceError(err),
+            libc::ENOSYS | libc::ENOTTY | libc::EOPNOTSUPP => {
+                Error::NotSupportedError(err)
+            },
+            _ => Error::IOError(err),
+        }
+    }
+
+    // Only the DRM master can use some ioctls, being denied one of them
+    // means we aren't the master.
+    pub(crate) fn into_not_master(self) -> Error {
+        match self {
+            Error::PermissionDeniedError(err) => Error::NotMasterError(err),
+            err => err,
+        }
+    }
+
+    pub fn get_kernel_error(&self) -> Option<&KernelError> {
+        match self {
+            Error::BusyError(err) |
+            Error::IOError(err) |
+            Error::InvalidArgumentError(err) |
+            Error::NoSpaceError(err) |
+            Error::NotMasterError(err) |
+            Error::NotSupportedError(err) |
+            Error::PermissionDeniedError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BusyError(err) |
+            Error::IOError(err) |
+            Error::InvalidArgumentError(err) |
+            Error::NoSpaceError(err) |
+            Error::NotSupportedError(err) |
+            Error::PermissionDeniedError(err) => write!(f, "{}", err),
+            Error::DuplicatePropertyError {
+                object_id,
+                property_id,
+            } => write!(
+                f,
+                "Property {} of object {} is already set",
+                property_id, object_id
+            ),
+            Error::InvalidConfigurationError => {
+                write!(f, "Invalid configuration")
+            },
+            Error::NoneError => write!(f, "Not found"),
+            Error::NotMasterError(err) => {
+                write!(f, "Not the DRM master ({})", err)
+            },
+            Error::OutOfRangeError => write!(f, "Value out of range"),
+            Error::PropertyNotFoundError {
+                object_id,
+                object_type,
+                property,
+            } => write!(
+                f,
+                "No property \"{}\" on {:?} {}",
+                property, object_type, object_id
+            ),
+            Error::PropertyNotQueuedError {
+                object_id,
+                property_id,
+            } => write!(
+                f,
+                "Property {} of object {} isn't part of the request",
+                property_id, object_id
+            ),
+            Error::StringConversionError => write!(f, "Invalid string"),
+            Error::TraceDivergenceError {
+                ioctl,
+            } => write!(f, "Replay diverged from the trace at {}", ioctl),
+            Error::UnsupportedError => write!(f, "Unsupported operation"),
+            Error::UninitializedError => write!(f, "Missing configuration"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Returns a closure turning the error of a failed ioctl into ours
+pub(crate) fn ioctl_error(
+    operation: &'static str,
+) -> impl Fn(std::io::Error) -> Error {
+    move |err| {
+        let errno = err.raw_os_error().unwrap_or(0);
+
+        Error::from_errno(operation, errno, None)
+    }
+}
+
+pub(crate) fn master_error(
+    operation: &'static str,
+) -> impl Fn(std::io::Error) -> Error {
+    move |err| ioctl_error(operation)(err).into_not_master()
+}
+
+pub(crate) fn master_object_error(
+    operation: &'static str,
+    object_id: u32,
+) -> impl Fn(std::io::Error) -> Error {
+    move |err| object_error(operation, object_id)(err).into_not_master()
+}
+
+pub(crate) fn object_error(
+    operation: &'static str,
+    object_id: u32,
+) -> impl Fn(std::io::Error) -> Error {
+    move |err| {
+        let errno = err.raw_os_error().unwrap_or(0);
+
+        Error::from_errno(operation, errno, Some(object_id))
+    }
+}
+
+pub(crate) fn property_not_found(
+    object: &impl Object,
+    property: &str,
+) -> Error {
+    Error::PropertyNotFoundError {
+        object_id:   object.get_id(),
+        object_type: object.get_type(),
+        property:    property.to_string(),
+    }
+}
+
 impl From<std::io::Error> for Error {
-    fn from(_err: std::io::Error) -> Self {
-        Error::IOError
+    fn from(err: std::io::Error) -> Self {
+        Error::from_errno("I/O", err.raw_os_error().unwrap_or(0), None)
     }
 }
 
 impl From<mmap::MapError> for Error {
-    fn from(_err: mmap::MapError) -> Self {
-        Error::IOError
+    fn from(err: mmap::MapError) -> Self {
+        let errno = match err {
+            mmap::MapError::ErrNoMem => libc::ENOMEM,
+            mmap::MapError::ErrUnknown(errno) => errno as i32,
+            _ => libc::EINVAL,
+        };
+
+        Error::from_errno("mmap", errno, None)
     }
 }
 
@@ -33,3 +256,69 @@ impl From<std::str::Utf8Error> for Error {
         Error::StringConversionError
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::simulated::fixtures;
+    use crate::AtomicRequest;
+    use crate::CommitOptions;
+    use crate::Object;
+    use crate::ObjectType;
+
+    use super::Error;
+
+    #[test]
+    fn test_error_context() {
+        let dev = fixtures::hdmi_device();
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+        let plane = crtc.get_possible_planes().unwrap().remove(0);
+
+        // An active CRTC without a mode can't be displayed
+        let request = AtomicRequest::new(&dev)
+            .add_property(&crtc, "ACTIVE", 1)
+            .unwrap();
+        let err = request.commit_with(CommitOptions::new()).unwrap_err();
+        assert!(matches!(err, Error::InvalidArgumentError(_)));
+
+        let kernel = err.get_kernel_error().unwrap();
+        assert_eq!(kernel.get_operation(), "ATOMIC");
+        assert_eq!(kernel.get_errno(), libc::EINVAL);
+
+        let err = request
+            .clone()
+            .add_property(&crtc, "ACTIVE", 0)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            Error::DuplicatePropertyError {
+                object_id:   crtc.get_id(),
+                property_id: crtc.get_property_id("ACTIVE").unwrap(),
+            }
+        );
+
+        let err = plane.get_property_id("ROTATION").unwrap_err();
+        assert_eq!(
+            err,
+            Error::PropertyNotFoundError {
+                object_id:   plane.get_id(),
+                object_type: ObjectType::Plane,
+                property:    String::from("ROTATION"),
+            }
+        );
+        assert!(err.to_string().contains("ROTATION"));
+    }
+    #[test]
+    fn test_error_not_master() {
+        let dev = fixtures::hdmi_device();
+        let buffer = fixtures::framebuffer(&dev);
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+
+        dev.drop_master().unwrap();
+        let err = dev.set_crtc(&buffer, &crtc, &[], 0, 0, None).unwrap_err();
+        assert!(matches!(err, Error::NotMasterError(_)));
+
+        let kernel = err.get_kernel_error().unwrap();
+        assert_eq!(kernel.get_operation(), "SETCRTC");
+        assert_eq!(kernel.get_errno(), libc::EACCES);
+    }
+}
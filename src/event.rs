@@ -0,0 +1,325 @@
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::error::Result;
+use crate::rawdevice::drm_event;
+use crate::rawdevice::drm_event_crtc_sequence;
+use crate::rawdevice::drm_event_vblank;
+use crate::rawdevice::DRM_EVENT_CRTC_SEQUENCE;
+use crate::rawdevice::DRM_EVENT_FLIP_COMPLETE;
+use crate::rawdevice::DRM_EVENT_VBLANK;
+
+fn read_struct<T>(data: &[u8]) -> Result<T> {
+    if data.len() < std::mem::size_of::<T>() {
+        return Err(Error::OutOfRangeError);
+    }
+
+    Ok(unsafe { std::ptr::read_unaligned(data.as_ptr() as *const T) })
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct VblankEvent {
+    crtc_id:   u32,
+    sequence:  u32,
+    tv_sec:    u32,
+    tv_usec:   u32,
+    user_data: u64,
+}
+
+impl VblankEvent {
+    fn new(event: drm_event_vblank) -> Self {
+        VblankEvent {
+            crtc_id:   event.crtc_id,
+            sequence:  event.sequence,
+            tv_sec:    event.tv_sec,
+            tv_usec:   event.tv_usec,
+            user_data: event.user_data,
+        }
+    }
+
+    pub fn get_crtc_id(&self) -> u32 {
+        self.crtc_id
+    }
+
+    pub fn get_sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    pub fn get_timestamp(&self) -> Duration {
+        Duration::new(self.tv_sec as u64, self.tv_usec * 1000)
+    }
+
+    pub fn get_user_data(&self) -> u64 {
+        self.user_data
+    }
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct SequenceEvent {
+    sequence:  u64,
+    time_ns:   i64,
+    user_data: u64,
+}
+
+impl SequenceEvent {
+    fn new(event: drm_event_crtc_sequence) -> Self {
+        SequenceEvent {
+            sequence:  event.sequence,
+            time_ns:   event.time_ns,
+            user_data: event.user_data,
+        }
+    }
+
+    pub fn get_sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn get_time_ns(&self) -> i64 {
+        self.time_ns
+    }
+
+    pub fn get_user_data(&self) -> u64 {
+        self.user_data
+    }
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum Event {
+    Vblank(VblankEvent),
+    FlipComplete(VblankEvent),
+    CrtcSequence(SequenceEvent),
+}
+
+fn parse_event(type_: u32, raw: &[u8]) -> Result<Option<Event>> {
+    let event = match type_ {
+        DRM_EVENT_VBLANK => Event::Vblank(VblankEvent::new(read_struct(raw)?)),
+        DRM_EVENT_FLIP_COMPLETE => {
+            Event::FlipComplete(VblankEvent::new(read_struct(raw)?))
+        },
+        DRM_EVENT_CRTC_SEQUENCE => {
+            Event::CrtcSequence(SequenceEvent::new(read_struct(raw)?))
+        },
+        // Drivers are free to send their own events, skip them
+        _ => return Ok(None),
+    };
+
+    Ok(Some(event))
+}
+
+// Returns the events found in data, how many bytes they took, and the error
+// for the first malformed event. Malformed events are skipped, and so is
+// everything after one whose length can't be trusted. Whatever is left is
+// the beginning of an event that hasn't been fully read yet.
+pub(crate) fn parse_events(data: &[u8]) -> (Vec<Event>, usize, Option<Error>) {
+    let header_len = std::mem::size_of::<drm_event>();
+    let mut events = Vec::new();
+    let mut error = None;
+    let mut offset = 0;
+
+    while data.len() - offset >= header_len {
+        let header: drm_event = match read_struct(&data[offset..]) {
+            Ok(header) => header,
+            Err(err) => return (events, data.len(), Some(err)),
+        };
+
+        let len = header.length as usize;
+        if len < header_len {
+            return (events, data.len(), Some(Error::OutOfRangeError));
+        }
+
+        if data.len() - offset < len {
+            break;
+        }
+
+        match parse_event(header.type_, &data[offset..offset + len]) {
+            Ok(Some(event)) => events.push(event),
+            Ok(None) => {},
+            Err(err) => {
+                error.get_or_insert(err);
+            },
+        }
+
+        offset += len;
+    }
+
+    (events, offset, error)
+}
+
+#[derive(Debug)]
+#[derive(Default)]
+pub(crate) struct EventReader {
+    pending: Vec<u8>,
+    error:   Option<Error>,
+}
+
+impl EventReader {
+    pub(crate) fn parse(&mut self, data: &[u8]) -> Result<Vec<Event>> {
+        self.pending.extend_from_slice(data);
+
+        // The events read along with a malformed one have been handed out
+        // first, it's now time to report it.
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+
+        let (events, len, error) = parse_events(&self.pending);
+        self.pending.drain(..len);
+
+        match error {
+            Some(err) if events.is_empty() => Err(err),
+            Some(err) => {
+                self.error = Some(err);
+                Ok(events)
+            },
+            None => Ok(events),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::rawdevice::DRM_EVENT_CRTC_SEQUENCE;
+    use crate::rawdevice::DRM_EVENT_FLIP_COMPLETE;
+    use crate::rawdevice::DRM_EVENT_VBLANK;
+
+    use super::parse_events;
+    use super::Event;
+    use super::EventReader;
+
+    fn vblank(type_: u32, crtc_id: u32, sequence: u32, data: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(&type_.to_ne_bytes());
+        bytes.extend_from_slice(&32u32.to_ne_bytes());
+        bytes.extend_from_slice(&data.to_ne_bytes());
+        bytes.extend_from_slice(&12u32.to_ne_bytes());
+        bytes.extend_from_slice(&500u32.to_ne_bytes());
+        bytes.extend_from_slice(&sequence.to_ne_bytes());
+        bytes.extend_from_slice(&crtc_id.to_ne_bytes());
+
+        bytes
+    }
+
+    fn sequence(sequence: u64, time_ns: i64, data: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(&DRM_EVENT_CRTC_SEQUENCE.to_ne_bytes());
+        bytes.extend_from_slice(&32u32.to_ne_bytes());
+        bytes.extend_from_slice(&data.to_ne_bytes());
+        bytes.extend_from_slice(&time_ns.to_ne_bytes());
+        bytes.extend_from_slice(&sequence.to_ne_bytes());
+
+        bytes
+    }
+
+    #[test]
+    fn test_parse_multiple_events() {
+        let mut data = vblank(DRM_EVENT_VBLANK, 42, 7, 0xdead);
+        data.extend(vblank(DRM_EVENT_FLIP_COMPLETE, 43, 8, 0xbeef));
+        data.extend(sequence(1000, 123456789, 0xcafe));
+
+        let (events, len, error) = parse_events(&data);
+        assert!(error.is_none());
+        assert_eq!(len, data.len());
+        assert_eq!(events.len(), 3);
+
+        match events[0] {
+            Event::Vblank(event) => {
+                assert_eq!(event.get_crtc_id(), 42);
+                assert_eq!(event.get_sequence(), 7);
+                assert_eq!(event.get_user_data(), 0xdead);
+                assert_eq!(
+                    event.get_timestamp(),
+                    Duration::from_micros(12_000_500)
+                );
+            },
+            _ => panic!("Expected a vblank event"),
+        }
+
+        match events[1] {
+            Event::FlipComplete(event) => {
+                assert_eq!(event.get_crtc_id(), 43);
+                assert_eq!(event.get_user_data(), 0xbeef);
+            },
+            _ => panic!("Expected a flip complete event"),
+        }
+
+        match events[2] {
+            Event::CrtcSequence(event) => {
+                assert_eq!(event.get_sequence(), 1000);
+                assert_eq!(event.get_time_ns(), 123456789);
+                assert_eq!(event.get_user_data(), 0xcafe);
+            },
+            _ => panic!("Expected a CRTC sequence event"),
+        }
+    }
+
+    #[test]
+    fn test_parse_partial_events() {
+        let mut data = vblank(DRM_EVENT_FLIP_COMPLETE, 42, 1, 1);
+        data.extend(vblank(DRM_EVENT_FLIP_COMPLETE, 42, 2, 2));
+
+        let mut reader = EventReader::default();
+
+        assert!(reader.parse(&data[..5]).unwrap().is_empty());
+
+        let events = reader.parse(&data[5..40]).unwrap();
+        assert_eq!(events.len(), 1);
+
+        let events = reader.parse(&data[40..]).unwrap();
+        match events[..] {
+            [Event::FlipComplete(event)] => assert_eq!(event.get_sequence(), 2),
+            _ => panic!("Expected a single flip complete event"),
+        }
+    }
+
+    #[test]
+    fn test_parse_unknown_and_malformed_events() {
+        let mut data = vblank(0x80000000, 42, 1, 1);
+        data.extend(vblank(DRM_EVENT_VBLANK, 42, 2, 2));
+
+        let (events, _, error) = parse_events(&data);
+        assert!(error.is_none());
+        assert_eq!(events.len(), 1);
+
+        let mut data = vblank(DRM_EVENT_VBLANK, 42, 1, 1);
+        data[4..8].copy_from_slice(&4u32.to_ne_bytes());
+        assert!(parse_events(&data).2.is_some());
+
+        let mut data = vblank(DRM_EVENT_VBLANK, 42, 1, 1);
+        data[4..8].copy_from_slice(&16u32.to_ne_bytes());
+        assert!(parse_events(&data[..16]).2.is_some());
+    }
+
+    #[test]
+    fn test_parse_events_around_malformed() {
+        let mut bad = vblank(DRM_EVENT_VBLANK, 42, 2, 2);
+        bad[4..8].copy_from_slice(&16u32.to_ne_bytes());
+
+        let mut data = vblank(DRM_EVENT_FLIP_COMPLETE, 42, 1, 1);
+        data.extend(&bad[..16]);
+        data.extend(vblank(DRM_EVENT_FLIP_COMPLETE, 42, 3, 3));
+
+        let (events, len, error) = parse_events(&data);
+        assert_eq!(events.len(), 2);
+        assert_eq!(len, data.len());
+        assert!(error.is_some());
+
+        // The good events come first, and the error with the next read
+        let mut reader = EventReader::default();
+        assert_eq!(reader.parse(&data).unwrap(), events);
+        assert!(reader.parse(&[]).is_err());
+        assert!(reader.parse(&[]).unwrap().is_empty());
+    }
+}
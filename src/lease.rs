@@ -0,0 +1,47 @@
+use std::os::unix::io::AsFd;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::OwnedFd;
+use std::os::unix::io::RawFd;
+
+use crate::device::Device;
+use crate::error::Result;
+
+#[derive(Debug)]
+pub struct Lease {
+    lessee_id: u32,
+    fd:        OwnedFd,
+}
+
+impl AsFd for Lease {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for Lease {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl Lease {
+    pub(crate) fn new(lessee_id: u32, fd: OwnedFd) -> Self {
+        Lease {
+            lessee_id,
+            fd,
+        }
+    }
+
+    pub fn get_lessee_id(&self) -> u32 {
+        self.lessee_id
+    }
+
+    pub fn into_device(self) -> Result<Device> {
+        Device::from_fd(self.fd)
+    }
+
+    pub fn into_fd(self) -> OwnedFd {
+        self.fd
+    }
+}
@@ -5,30 +5,68 @@
 extern crate vmm_sys_util;
 
 mod atomic;
+mod backend;
+mod blob;
 mod buffer;
 mod connector;
 mod crtc;
 mod device;
+mod driver;
 mod encoder;
+mod enumeration;
 mod error;
+mod event;
 mod format;
+mod lease;
 mod mode;
 mod object;
 mod pipeline;
 mod plane;
 mod property;
 mod rawdevice;
+mod simulated;
+mod state;
+mod trace;
 
+pub use crate::atomic::AtomicDiagnosis;
+pub use crate::atomic::AtomicProperty;
+pub use crate::atomic::AtomicRejection;
+pub use crate::atomic::AtomicRequest;
+pub use crate::atomic::CheckResult;
+pub use crate::atomic::CommitOptions;
+pub use crate::blob::PropertyBlob;
 pub use crate::buffer::Buffer;
 pub use crate::buffer::BufferType;
 pub use crate::connector::Connector;
 pub use crate::connector::ConnectorStatus;
 pub use crate::connector::ConnectorType;
+pub use crate::device::Capability;
 pub use crate::device::ClientCapability;
 pub use crate::device::Device;
+pub use crate::device::DeviceBuilder;
+pub use crate::device::DeviceCapability;
+pub use crate::driver::DriverInfo;
+pub use crate::enumeration::DeviceEnumerator;
+pub use crate::enumeration::DeviceInfo;
+pub use crate::enumeration::DeviceNodeType;
+pub use crate::error::Error;
+pub use crate::error::KernelError;
 pub use crate::error::Result;
+pub use crate::event::Event;
+pub use crate::event::SequenceEvent;
+pub use crate::event::VblankEvent;
 pub use crate::format::Format;
+pub use crate::lease::Lease;
 pub use crate::mode::Mode;
 pub use crate::mode::ModeType;
+pub use crate::object::Object;
+pub use crate::object::ObjectType;
 pub use crate::pipeline::Pipeline;
 pub use crate::pipeline::PipelineInit;
+pub use crate::property::Property;
+pub use crate::property::PropertyKind;
+pub use crate::property::PropertyValue;
+pub use crate::simulated::SimulatedDevice;
+pub use crate::state::StateGuard;
+pub use crate::trace::Trace;
+pub use crate::trace::TraceEntry;
@@ -1,8 +1,18 @@
+use num_enum::TryFromPrimitive;
+
 use crate::device::Device;
-use crate::error::Error;
+use crate::error::property_not_found;
 use crate::error::Result;
+use crate::property::Property;
+use crate::property::PropertyValue;
 
+#[derive(Clone)]
+#[derive(Copy)]
 #[derive(Debug)]
+#[derive(PartialEq)]
+#[derive(TryFromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(u32)]
 pub enum ObjectType {
     Any       = 0,
@@ -21,6 +31,13 @@ pub trait Object {
     fn get_id(&self) -> u32;
     fn get_type(&self) -> ObjectType;
 
+    fn get_properties(&self) -> Result<Vec<(Property, PropertyValue)>>
+    where
+        Self: Sized,
+    {
+        self.get_dev().get_property_values(self)
+    }
+
     fn get_property_id(&self, property: &str) -> Result<u32>
     where
         Self: Sized,
@@ -31,7 +48,19 @@ pub trait Object {
             .get_properties(self)?
             .iter()
             .find(|prop| prop.get_name() == property)
-            .ok_or(Error::NoneError)?
+            .ok_or_else(|| property_not_found(self, property))?
             .get_id())
     }
+
+    fn get_property_value(&self, property: &str) -> Result<PropertyValue>
+    where
+        Self: Sized,
+    {
+        Ok(self
+            .get_properties()?
+            .into_iter()
+            .find(|(prop, _)| prop.get_name() == property)
+            .ok_or_else(|| property_not_found(self, property))?
+            .1)
+    }
 }
@@ -1,4 +1,10 @@
+use std::sync::Arc;
+
+use crate::atomic::AtomicDiagnosis;
 use crate::atomic::AtomicRequest;
+use crate::atomic::CheckResult;
+use crate::atomic::CommitOptions;
+use crate::blob::PropertyBlob;
 use crate::buffer::Buffer;
 use crate::connector::Connector;
 use crate::crtc::Crtc;
@@ -9,84 +15,74 @@ use crate::error::Result;
 use crate::mode::Mode;
 use crate::object::Object;
 use crate::plane::Plane;
+use crate::property::PropertyValue;
 
 #[derive(Debug)]
-pub struct PipelineInit<'a> {
-    dev:       &'a Device,
-    buffer:    Option<&'a Buffer<'a>>,
+pub struct PipelineInit {
+    dev:       Device,
+    buffer:    Option<Arc<Buffer>>,
     mode:      Option<Mode>,
-    plane:     Option<Plane<'a>>,
+    plane:     Option<Plane>,
     plane_x:   isize,
     plane_y:   isize,
     plane_h:   Option<usize>,
     plane_w:   Option<usize>,
-    crtc:      Option<Crtc<'a>>,
-    encoder:   Option<Encoder<'a>>,
-    connector: Option<Connector<'a>>,
-    request:   AtomicRequest<'a>,
+    crtc:      Option<Crtc>,
+    encoder:   Option<Encoder>,
+    connector: Option<Connector>,
+    request:   AtomicRequest,
 }
 
-impl<'a> PipelineInit<'a> {
-    pub fn build(mut self) -> Result<Pipeline<'a>> {
-        if self.buffer.is_none() ||
-            self.mode.is_none() ||
-            self.plane.is_none() ||
-            self.crtc.is_none() ||
-            self.encoder.is_none() ||
-            self.connector.is_none()
-        {
+impl PipelineInit {
+    pub fn build(self) -> Result<Pipeline> {
+        if !self.is_complete() {
             return Err(Error::UninitializedError);
         }
 
-        let buffer = self.buffer.unwrap();
-        let mode = self.mode.unwrap();
-        let plane = self.plane.unwrap();
-        let crtc = self.crtc.unwrap();
-        let connector = self.connector.unwrap();
-
-        let bh = buffer.get_height();
-        let bw = buffer.get_width();
-        let ph = match self.plane_h {
-            Some(x) => x,
-            None => bh,
-        };
-        let pw = match self.plane_w {
-            Some(x) => x,
-            None => bw,
-        };
-        let mode_id = self.dev.raw.create_property_blob(&mode.into_inner())?;
-        let fb_id = buffer.get_framebuffer_id().ok_or(Error::NoneError)?;
-
-        self.request = self.request
-            .add_property(&plane, "FB_ID", fb_id as u64)?
-            .add_property(&plane, "CRTC_ID", crtc.get_id() as u64)?
-            .add_property(&plane, "SRC_X", 0)?
-            .add_property(&plane, "SRC_Y", 0)?
-            .add_property(&plane, "SRC_H", (bh << 16) as u64)?
-            .add_property(&plane, "SRC_W", (bw << 16) as u64)?
-            .add_property(&plane, "CRTC_X", self.plane_x as u64)?
-            .add_property(&plane, "CRTC_Y", self.plane_y as u64)?
-            .add_property(&plane, "CRTC_H", ph as u64)?
-            .add_property(&plane, "CRTC_W", pw as u64)?
-            .add_property(&crtc, "MODE_ID", mode_id as u64)?
-            .add_property(&crtc, "ACTIVE", 1)?
-            .add_property(&connector, "CRTC_ID", crtc.get_id() as u64)?;
-
-        self.request.commit()?;
+        let mode_blob = self.create_mode_blob()?;
+        let request = self.prepare_request(mode_blob.get_id())?;
+
+        // Some of the extra properties might still need a modeset, in
+        // which case the commit is rejected without doing anything.
+        if self.needs_modeset(&mode_blob)? {
+            request.commit()?;
+        } else {
+            match request.commit_with(CommitOptions::new()) {
+                Err(Error::InvalidArgumentError(_)) => request.commit()?,
+                result => result?,
+            }
+        }
 
         Ok(Pipeline {
             dev: self.dev,
-            buffer,
-            plane,
-            crtc,
-            request: self.request,
+            buffer: self.buffer.unwrap(),
+            plane: self.plane.unwrap(),
+            crtc: self.crtc.unwrap(),
+            previous: None,
+            flipping: None,
+            _mode_blob: mode_blob,
+            request,
         })
     }
 
+    pub fn check(&self) -> Result<CheckResult> {
+        self.with_test_request(|request| request.test())
+    }
+
+    fn create_mode_blob(&self) -> Result<PropertyBlob> {
+        let mode = self.mode.as_ref().ok_or(Error::UninitializedError)?;
+
+        PropertyBlob::from_mode(&self.dev, mode)
+    }
+
+    pub fn diagnose(&self) -> Result<AtomicDiagnosis> {
+        self.with_test_request(|request| request.diagnose())
+    }
+
     pub fn discover(
         mut self,
-        connector: Connector<'a>,
-    ) -> Result<PipelineInit<'a>> {
+        connector: Connector,
+    ) -> Result<PipelineInit> {
         let encoders = connector.get_encoders()?;
         let encoder = encoders.into_iter().next().ok_or(Error::NoneError)?;
 
@@ -104,9 +100,9 @@ impl<'a> PipelineInit<'a> {
         Ok(self)
     }
 
-    pub fn new(dev: &'a Device) -> PipelineInit<'a> {
+    pub fn new(dev: &Device) -> PipelineInit {
         PipelineInit {
-            dev,
+            dev: dev.clone(),
             buffer: None,
             mode: None,
             plane: None,
@@ -121,41 +117,127 @@ impl<'a> PipelineInit<'a> {
         }
     }
 
-    pub fn add_property(mut self, object: &impl Object, property: &str, value: u64) -> Result<PipelineInit<'a>> {
+    fn is_complete(&self) -> bool {
+        self.buffer.is_some() &&
+            self.mode.is_some() &&
+            self.plane.is_some() &&
+            self.crtc.is_some() &&
+            self.encoder.is_some() &&
+            self.connector.is_some()
+    }
+
+    // Keeping the output on, with the same mode, doesn't need a modeset
+    fn needs_modeset(&self, mode_blob: &PropertyBlob) -> Result<bool> {
+        let crtc = self.crtc.as_ref().ok_or(Error::UninitializedError)?;
+        let connector =
+            self.connector.as_ref().ok_or(Error::UninitializedError)?;
+
+        let active = crtc.get_property_value("ACTIVE")?;
+        let routed = connector.get_property_value("CRTC_ID")?;
+        if active != PropertyValue::Range(1) ||
+            routed != PropertyValue::Object(crtc.get_id())
+        {
+            return Ok(true);
+        }
+
+        let mode_id = match crtc.get_property_value("MODE_ID")? {
+            PropertyValue::Blob(id) if id != 0 => id,
+            _ => return Ok(true),
+        };
+
+        if mode_id == mode_blob.get_id() {
+            return Ok(false);
+        }
+
+        Ok(self.dev.get_blob(mode_id)? != mode_blob.get_data()?)
+    }
+
+    fn prepare_request(&self, mode_id: u32) -> Result<AtomicRequest> {
+        let buffer = self.buffer.as_ref().ok_or(Error::UninitializedError)?;
+        let plane = self.plane.as_ref().ok_or(Error::UninitializedError)?;
+        let crtc = self.crtc.as_ref().ok_or(Error::UninitializedError)?;
+        let connector =
+            self.connector.as_ref().ok_or(Error::UninitializedError)?;
+
+        let bh = buffer.get_height();
+        let bw = buffer.get_width();
+        let ph = match self.plane_h {
+            Some(x) => x,
+            None => bh,
+        };
+        let pw = match self.plane_w {
+            Some(x) => x,
+            None => bw,
+        };
+        let fb_id = buffer.get_framebuffer_id().ok_or(Error::NoneError)?;
+
+        self.request
+            .clone()
+            .add_property(plane, "FB_ID", fb_id as u64)?
+            .add_property(plane, "CRTC_ID", crtc.get_id() as u64)?
+            .add_property(plane, "SRC_X", 0)?
+            .add_property(plane, "SRC_Y", 0)?
+            .add_property(plane, "SRC_H", (bh << 16) as u64)?
+            .add_property(plane, "SRC_W", (bw << 16) as u64)?
+            .add_property(plane, "CRTC_X", self.plane_x as u64)?
+            .add_property(plane, "CRTC_Y", self.plane_y as u64)?
+            .add_property(plane, "CRTC_H", ph as u64)?
+            .add_property(plane, "CRTC_W", pw as u64)?
+            .add_property(crtc, "MODE_ID", mode_id as u64)?
+            .add_property(crtc, "ACTIVE", 1)?
+            .add_property(connector, "CRTC_ID", crtc.get_id() as u64)
+    }
+
+    // The mode blob only lives for as long as the request is being tested
+    fn with_test_request<T>(
+        &self,
+        test: impl FnOnce(&AtomicRequest) -> Result<T>,
+    ) -> Result<T> {
+        if !self.is_complete() {
+            return Err(Error::UninitializedError);
+        }
+
+        let mode_blob = self.create_mode_blob()?;
+
+        self.prepare_request(mode_blob.get_id())
+            .and_then(|request| test(&request))
+    }
+
+    pub fn add_property(mut self, object: &impl Object, property: &str, value: u64) -> Result<PipelineInit> {
         self.request = self.request.add_property(object, property, value)?;
         Ok(self)
     }
 
 
-    pub fn set_buffer(mut self, buffer: &'a Buffer<'_>) -> PipelineInit<'a> {
+    pub fn set_buffer(mut self, buffer: Arc<Buffer>) -> PipelineInit {
         self.buffer = Some(buffer);
         self
     }
 
     pub fn set_connector(
         mut self,
-        connector: Connector<'a>,
-    ) -> PipelineInit<'a> {
+        connector: Connector,
+    ) -> PipelineInit {
         self.connector = Some(connector);
         self
     }
 
-    pub fn set_crtc(mut self, crtc: Crtc<'a>) -> PipelineInit<'a> {
+    pub fn set_crtc(mut self, crtc: Crtc) -> PipelineInit {
         self.crtc = Some(crtc);
         self
     }
 
-    pub fn set_encoder(mut self, encoder: Encoder<'a>) -> PipelineInit<'a> {
+    pub fn set_encoder(mut self, encoder: Encoder) -> PipelineInit {
         self.encoder = Some(encoder);
         self
     }
 
-    pub fn set_mode(mut self, mode: Mode) -> PipelineInit<'a> {
+    pub fn set_mode(mut self, mode: Mode) -> PipelineInit {
         self.mode = Some(mode);
         self
     }
 
-    pub fn set_plane(mut self, plane: Plane<'a>) -> PipelineInit<'a> {
+    pub fn set_plane(mut self, plane: Plane) -> PipelineInit {
         self.plane = Some(plane);
         self
     }
@@ -164,7 +246,7 @@ impl<'a> PipelineInit<'a> {
         mut self,
         x: isize,
         y: isize,
-    ) -> PipelineInit<'a> {
+    ) -> PipelineInit {
         self.plane_x = x;
         self.plane_y = y;
         self
@@ -174,7 +256,7 @@ impl<'a> PipelineInit<'a> {
         mut self,
         w: usize,
         h: usize,
-    ) -> PipelineInit<'a> {
+    ) -> PipelineInit {
         self.plane_h = Some(h);
         self.plane_w = Some(w);
         self
@@ -182,21 +264,45 @@ impl<'a> PipelineInit<'a> {
 }
 
 #[derive(Debug)]
-pub struct Pipeline<'a> {
-    dev:     &'a Device,
-    buffer:  &'a Buffer<'a>,
-    plane:   Plane<'a>,
-    crtc:    Crtc<'a>,
-    request: AtomicRequest<'a>,
+pub struct Pipeline {
+    dev:      Device,
+    buffer:   Arc<Buffer>,
+    plane:    Plane,
+    crtc:     Crtc,
+    request:  AtomicRequest,
+
+    // Still displayed until the new buffer has been committed
+    previous: Option<Arc<Buffer>>,
+
+    // Replaced by a nonblocking commit, but maybe not off the screen yet
+    flipping: Option<Arc<Buffer>>,
+
+    // Only held so that the CRTC mode outlives the pipeline's commits
+    _mode_blob: PropertyBlob,
 }
 
-impl<'a> Pipeline<'a> {
-    pub fn commit(self) -> Result<Pipeline<'a>> {
-        self.request.commit()?;
+impl Pipeline {
+    pub fn commit(self) -> Result<Pipeline> {
+        self.commit_with(CommitOptions::new())
+    }
+
+    // A nonblocking commit returns before the flip, so the buffer it
+    // replaces is only released by the next commit: the kernel doesn't
+    // take a new one until the previous flip is done.
+    pub fn commit_with(mut self, options: CommitOptions) -> Result<Pipeline> {
+        self.request.commit_with(options)?;
+
+        let previous = self.previous.take();
+        self.flipping = if options.is_nonblocking() {
+            previous
+        } else {
+            None
+        };
+
         Ok(self)
     }
 
-    pub fn update_buffer(mut self, buffer: &'a Buffer<'a>) -> Result<Pipeline<'a>> {
+    pub fn update_buffer(mut self, buffer: Arc<Buffer>) -> Result<Pipeline> {
         let fb_id = buffer
             .get_framebuffer_id()
             .ok_or(Error::UninitializedError)?;
@@ -205,8 +311,13 @@ impl<'a> Pipeline<'a> {
             self.request
                 .update_property(&self.plane, "FB_ID", fb_id as u64)?;
 
+        // Only the buffer on screen has to outlive the next commit
+        let current = std::mem::replace(&mut self.buffer, buffer);
+        if self.previous.is_none() {
+            self.previous = Some(current);
+        }
+
         self.request = request;
-        self.buffer = buffer;
         Ok(self)
     }
 
@@ -214,7 +325,7 @@ impl<'a> Pipeline<'a> {
         mut self,
         x: isize,
         y: isize,
-    ) -> Result<Pipeline<'a>> {
+    ) -> Result<Pipeline> {
         let request = self.request
             .update_property(&self.plane, "CRTC_X", x as u64)?
             .update_property(&self.plane, "CRTC_Y", y as u64)?;
@@ -227,7 +338,7 @@ impl<'a> Pipeline<'a> {
         mut self,
         w: usize,
         h: usize,
-    ) -> Result<Pipeline<'a>> {
+    ) -> Result<Pipeline> {
         let request = self.request
             .update_property(&self.plane, "SRC_H", (h << 16) as u64)?
             .update_property(&self.plane, "SRC_W", (w << 16) as u64)?;
@@ -240,7 +351,7 @@ impl<'a> Pipeline<'a> {
         mut self,
         w: usize,
         h: usize,
-    ) -> Result<Pipeline<'a>> {
+    ) -> Result<Pipeline> {
         let request = self.request
             .update_property(&self.plane, "CRTC_H", h as u64)?
             .update_property(&self.plane, "CRTC_W", w as u64)?;
@@ -249,3 +360,105 @@ impl<'a> Pipeline<'a> {
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::simulated::fixtures;
+    use crate::BufferType;
+    use crate::CommitOptions;
+    use crate::Format;
+    use crate::Object;
+    use crate::PropertyValue;
+
+    #[test]
+    fn test_pipeline_buffer_lifetime() {
+        let dev = fixtures::hdmi_device();
+        let plane = dev.get_planes().unwrap().remove(0);
+        let displayed = || match plane.get_property_value("FB_ID").unwrap() {
+            PropertyValue::Object(id) => id,
+            value => panic!("Unexpected property value {:?}", value),
+        };
+
+        let front = fixtures::framebuffer(&dev);
+        let front_id = front.get_framebuffer_id().unwrap();
+        let pipeline = fixtures::pipeline_init(&dev, &front).build().unwrap();
+
+        // The pipeline keeps the buffer it displays around
+        drop(front);
+        assert_eq!(displayed(), front_id);
+
+        let back = fixtures::framebuffer(&dev);
+        let back_id = back.get_framebuffer_id().unwrap();
+        let pipeline = pipeline.update_buffer(back).unwrap();
+        assert_eq!(displayed(), front_id);
+
+        let _pipeline = pipeline.commit().unwrap();
+        assert_eq!(displayed(), back_id);
+    }
+
+    #[test]
+    fn test_pipeline_buffer_lifetime_nonblocking() {
+        let dev = fixtures::hdmi_output()
+            .set_tracing(true)
+            .into_device()
+            .unwrap();
+        let removed = || {
+            let trace = dev.take_trace().unwrap();
+
+            trace.iter().filter(|entry| entry.get_ioctl() == "RMFB").count()
+        };
+
+        let front = fixtures::framebuffer(&dev);
+        let pipeline = fixtures::pipeline_init(&dev, &front).build().unwrap();
+        drop(front);
+
+        // The flip away from the front buffer might still be pending
+        let options = CommitOptions::new().set_nonblocking(true);
+        let pipeline = pipeline
+            .update_buffer(fixtures::framebuffer(&dev))
+            .unwrap()
+            .commit_with(options)
+            .unwrap();
+        assert_eq!(removed(), 0);
+
+        let _pipeline = pipeline.commit_with(options).unwrap();
+        assert_eq!(removed(), 1);
+    }
+
+    #[test]
+    fn test_pipeline_thread() {
+        let dev = fixtures::hdmi_device();
+        let front = fixtures::framebuffer(&dev);
+        let mut back = dev
+            .allocate_buffer(BufferType::Dumb, 1280, 720, 32)
+            .unwrap()
+            .map()
+            .unwrap()
+            .into_framebuffer(Format::XRGB8888)
+            .unwrap();
+        let fb_id = back.get_framebuffer_id().unwrap();
+
+        let pipeline = fixtures::pipeline_init(&dev, &front).build().unwrap();
+
+        // The objects keep the device around on their own
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+        drop(dev);
+
+        let render = std::thread::spawn(move || {
+            back.get_data().unwrap().fill(0xff);
+
+            let pipeline = pipeline.update_buffer(Arc::new(back)).unwrap();
+            pipeline.commit().unwrap()
+        });
+        let _pipeline = render.join().unwrap();
+
+        assert_eq!(
+            crtc.get_possible_planes().unwrap()[0]
+                .get_property_value("FB_ID")
+                .unwrap(),
+            PropertyValue::Object(fb_id)
+        );
+    }
+}
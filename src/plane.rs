@@ -6,14 +6,14 @@ use crate::object::ObjectType;
 use crate::rawdevice::drm_mode_get_plane;
 
 #[derive(Debug)]
-pub struct Plane<'a> {
-    dev: &'a Device,
+pub struct Plane {
+    dev: Device,
     id:  u32,
 }
 
-impl<'a> Object for Plane<'a> {
+impl Object for Plane {
     fn get_dev(&self) -> &Device {
-        self.dev
+        &self.dev
     }
 
     fn get_id(&self) -> u32 {
@@ -25,19 +25,19 @@ impl<'a> Object for Plane<'a> {
     }
 }
 
-impl<'a> Plane<'a> {
+impl Plane {
     pub(crate) fn new(
-        dev: &'a Device,
+        dev: &Device,
         plane: drm_mode_get_plane,
-    ) -> Result<Plane<'_>> {
+    ) -> Result<Plane> {
         Ok(Plane {
-            dev,
+            dev: dev.clone(),
             id: plane.plane_id,
         })
     }
 
-    pub fn get_possible_crtcs(&self) -> Result<Vec<Crtc<'_>>> {
-        let plane = self.dev.raw.get_plane(self.id)?;
+    pub fn get_possible_crtcs(&self) -> Result<Vec<Crtc>> {
+        let plane = self.dev.raw().get_plane(self.id)?;
         let crtcs = self.dev.get_crtcs()?;
 
         let ret = crtcs
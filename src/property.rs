@@ -1,20 +1,168 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::convert::TryInto;
+
 use crate::device::Device;
+use crate::error::property_not_found;
 use crate::error::Error;
 use crate::error::Result;
 use crate::object::Object;
 use crate::object::ObjectType;
 use crate::rawdevice::drm_mode_get_property;
+use crate::rawdevice::drm_mode_property_enum;
+use crate::rawdevice::DRM_MODE_PROP_ATOMIC;
+use crate::rawdevice::DRM_MODE_PROP_BITMASK;
+use crate::rawdevice::DRM_MODE_PROP_BLOB;
+use crate::rawdevice::DRM_MODE_PROP_ENUM;
+use crate::rawdevice::DRM_MODE_PROP_EXTENDED_TYPE;
+use crate::rawdevice::DRM_MODE_PROP_IMMUTABLE;
+use crate::rawdevice::DRM_MODE_PROP_OBJECT;
+use crate::rawdevice::DRM_MODE_PROP_RANGE;
+use crate::rawdevice::DRM_MODE_PROP_SIGNED_RANGE;
+
+fn parse_name(name: &[u8]) -> Result<String> {
+    Ok(std::str::from_utf8(name)?
+        .trim_end_matches(char::from(0))
+        .to_string())
+}
+
+fn parse_enums(enums: &[drm_mode_property_enum]) -> Result<Vec<(String, u64)>> {
+    enums
+        .iter()
+        .map(|entry| Ok((parse_name(&entry.name)?, entry.value)))
+        .collect()
+}
+
+// The list of properties of an object and their metadata never change
+// for as long as the object exists, so we only ask the kernel once.
+#[derive(Debug)]
+#[derive(Default)]
+pub(crate) struct PropertyCache {
+    objects:    HashMap<(u32, u32), Vec<u32>>,
+    properties: HashMap<u32, RawProperty>,
+}
+
+impl PropertyCache {
+    pub(crate) fn clear(&mut self) {
+        self.objects.clear();
+        self.properties.clear();
+    }
+
+    pub(crate) fn get_object(&self, type_: u32, id: u32) -> Option<&[u32]> {
+        self.objects.get(&(type_, id)).map(|ids| ids.as_slice())
+    }
+
+    pub(crate) fn get_property(&self, id: u32) -> Option<&RawProperty> {
+        self.properties.get(&id)
+    }
+
+    pub(crate) fn insert_object(&mut self, type_: u32, id: u32, props: &[u32]) {
+        self.objects.insert((type_, id), props.to_vec());
+    }
+
+    pub(crate) fn insert_property(&mut self, property: RawProperty) {
+        self.properties.insert(property.property.prop_id, property);
+    }
+}
 
+#[derive(Clone)]
 #[derive(Debug)]
-pub struct Property<'a> {
-    dev:  &'a Device,
-    id:   u32,
-    name: String,
+pub(crate) struct RawProperty {
+    pub(crate) property: drm_mode_get_property,
+    pub(crate) values:   Vec<u64>,
+    pub(crate) enums:    Vec<drm_mode_property_enum>,
 }
 
-impl<'a> Object for Property<'a> {
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum PropertyKind {
+    Range { min: u64, max: u64 },
+    SignedRange { min: i64, max: i64 },
+    Enum(Vec<(String, u64)>),
+    Bitmask(Vec<(String, u64)>),
+    Blob,
+    Object(ObjectType),
+    Unknown(u32),
+}
+
+impl PropertyKind {
+    fn new(
+        flags: u32,
+        values: &[u64],
+        enums: &[drm_mode_property_enum],
+    ) -> Result<PropertyKind> {
+        let ext_type = flags & DRM_MODE_PROP_EXTENDED_TYPE;
+        let value =
+            |idx: usize| values.get(idx).copied().ok_or(Error::NoneError);
+
+        let kind = if flags & DRM_MODE_PROP_RANGE != 0 {
+            PropertyKind::Range {
+                min: value(0)?,
+                max: value(1)?,
+            }
+        } else if ext_type == DRM_MODE_PROP_SIGNED_RANGE {
+            PropertyKind::SignedRange {
+                min: value(0)? as i64,
+                max: value(1)? as i64,
+            }
+        } else if flags & DRM_MODE_PROP_ENUM != 0 {
+            PropertyKind::Enum(parse_enums(enums)?)
+        } else if flags & DRM_MODE_PROP_BITMASK != 0 {
+            // The kernel gives us bit numbers, but what users need are the
+            // values to OR together.
+            let bits = parse_enums(enums)?
+                .into_iter()
+                .map(|(name, bit)| {
+                    let mask = 1u64
+                        .checked_shl(bit.try_into()?)
+                        .ok_or(Error::OutOfRangeError)?;
+
+                    Ok((name, mask))
+                })
+                .collect::<Result<_>>()?;
+
+            PropertyKind::Bitmask(bits)
+        } else if flags & DRM_MODE_PROP_BLOB != 0 {
+            PropertyKind::Blob
+        } else if ext_type == DRM_MODE_PROP_OBJECT {
+            let type_ = ObjectType::try_from(value(0)? as u32)
+                .map_err(|_| Error::OutOfRangeError)?;
+
+            PropertyKind::Object(type_)
+        } else {
+            PropertyKind::Unknown(flags & !DRM_MODE_PROP_ATOMIC)
+        };
+
+        Ok(kind)
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum PropertyValue {
+    Range(u64),
+    SignedRange(i64),
+    Enum(String),
+    Bitmask(Vec<String>),
+    Blob(u32),
+    Object(u32),
+    Unknown(u64),
+}
+
+#[derive(Debug)]
+pub struct Property {
+    dev:   Device,
+    id:    u32,
+    name:  String,
+    flags: u32,
+    kind:  PropertyKind,
+}
+
+impl Object for Property {
     fn get_dev(&self) -> &Device {
-        self.dev
+        &self.dev
     }
 
     fn get_id(&self) -> u32 {
@@ -32,28 +180,270 @@ impl<'a> Object for Property<'a> {
             .get_properties(self)?
             .iter()
             .find(|prop| prop.get_name() == property)
-            .ok_or(Error::NoneError)?
+            .ok_or_else(|| property_not_found(self, property))?
             .get_id())
     }
 }
 
-impl<'a> Property<'a> {
+impl Property {
     pub(crate) fn new(
-        dev: &'a Device,
-        property: drm_mode_get_property,
-    ) -> Result<Property<'_>> {
-        let name = std::str::from_utf8(&property.name)?
-            .trim_end_matches(char::from(0))
-            .to_string();
+        dev: &Device,
+        raw: &RawProperty,
+    ) -> Result<Property> {
+        let property = &raw.property;
 
         Ok(Property {
-            dev,
-            name,
+            dev: dev.clone(),
+            name: parse_name(&property.name)?,
             id: property.prop_id,
+            flags: property.flags,
+            kind: PropertyKind::new(property.flags, &raw.values, &raw.enums)?,
         })
     }
 
-    pub(crate) fn get_name(&self) -> &str {
+    pub fn decode(&self, value: u64) -> PropertyValue {
+        match &self.kind {
+            PropertyKind::Range {
+                ..
+            } => PropertyValue::Range(value),
+            PropertyKind::SignedRange {
+                ..
+            } => PropertyValue::SignedRange(value as i64),
+            PropertyKind::Enum(enums) => enums
+                .iter()
+                .find(|(_, val)| *val == value)
+                .map_or(PropertyValue::Unknown(value), |(name, _)| {
+                    PropertyValue::Enum(name.clone())
+                }),
+            PropertyKind::Bitmask(bits) => PropertyValue::Bitmask(
+                bits.iter()
+                    .filter(|(_, mask)| value & mask != 0)
+                    .map(|(name, _)| name.clone())
+                    .collect(),
+            ),
+            PropertyKind::Blob => PropertyValue::Blob(value as u32),
+            PropertyKind::Object(_) => PropertyValue::Object(value as u32),
+            PropertyKind::Unknown(_) => PropertyValue::Unknown(value),
+        }
+    }
+
+    pub fn encode(&self, value: &PropertyValue) -> Result<u64> {
+        if self.is_immutable() {
+            return Err(Error::InvalidConfigurationError);
+        }
+
+        let raw = match (&self.kind, value) {
+            (
+                PropertyKind::Range {
+                    min,
+                    max,
+                },
+                PropertyValue::Range(value),
+            ) => {
+                if value < min || value > max {
+                    return Err(Error::OutOfRangeError);
+                }
+
+                *value
+            },
+            (
+                PropertyKind::SignedRange {
+                    min,
+                    max,
+                },
+                PropertyValue::SignedRange(value),
+            ) => {
+                if value < min || value > max {
+                    return Err(Error::OutOfRangeError);
+                }
+
+                *value as u64
+            },
+            (PropertyKind::Enum(enums), PropertyValue::Enum(name)) => {
+                enums
+                    .iter()
+                    .find(|(entry, _)| entry == name)
+                    .ok_or(Error::NoneError)?
+                    .1
+            },
+            (PropertyKind::Bitmask(bits), PropertyValue::Bitmask(names)) => {
+                let mut mask = 0;
+
+                for name in names {
+                    mask |= bits
+                        .iter()
+                        .find(|(entry, _)| entry == name)
+                        .ok_or(Error::NoneError)?
+                        .1;
+                }
+
+                mask
+            },
+            (PropertyKind::Blob, PropertyValue::Blob(id)) => *id as u64,
+            (PropertyKind::Object(_), PropertyValue::Object(id)) => *id as u64,
+            (PropertyKind::Unknown(_), PropertyValue::Unknown(value)) => *value,
+            _ => return Err(Error::InvalidConfigurationError),
+        };
+
+        Ok(raw)
+    }
+
+    pub fn get_kind(&self) -> &PropertyKind {
+        &self.kind
+    }
+
+    pub fn get_name(&self) -> &str {
         &self.name
     }
+
+    pub fn is_atomic(&self) -> bool {
+        self.flags & DRM_MODE_PROP_ATOMIC != 0
+    }
+
+    pub fn is_immutable(&self) -> bool {
+        self.flags & DRM_MODE_PROP_IMMUTABLE != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulated::fixtures;
+    use crate::Object;
+    use crate::ObjectType;
+
+    use super::PropertyKind;
+    use super::PropertyValue;
+
+    #[test]
+    fn test_property_metadata() {
+        let dev = fixtures::hdmi_device();
+        let plane = dev.get_planes().unwrap().remove(0);
+        let properties = dev.get_properties(&plane).unwrap();
+        let kind = |name| {
+            properties
+                .iter()
+                .find(|prop| prop.get_name() == name)
+                .unwrap()
+                .get_kind()
+                .clone()
+        };
+
+        assert_eq!(kind("SRC_X"), PropertyKind::Range {
+            min: 0,
+            max: u32::MAX as u64,
+        });
+        assert_eq!(kind("CRTC_X"), PropertyKind::SignedRange {
+            min: i32::MIN as i64,
+            max: i32::MAX as i64,
+        });
+        assert_eq!(kind("FB_ID"), PropertyKind::Object(ObjectType::Fb));
+        assert_eq!(
+            kind("pixel blend mode"),
+            PropertyKind::Enum(vec![
+                (String::from("None"), 0),
+                (String::from("Pre-multiplied"), 1),
+                (String::from("Coverage"), 2),
+            ])
+        );
+
+        match kind("rotation") {
+            PropertyKind::Bitmask(bits) => {
+                assert_eq!(bits[1], (String::from("rotate-90"), 1 << 1));
+                assert_eq!(bits[5], (String::from("reflect-y"), 1 << 5));
+            },
+            kind => panic!("Unexpected property kind {:?}", kind),
+        }
+
+        let type_ = properties
+            .iter()
+            .find(|prop| prop.get_name() == "type")
+            .unwrap();
+        assert!(type_.is_immutable());
+
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+        let mode_id = dev
+            .get_properties(&crtc)
+            .unwrap()
+            .into_iter()
+            .find(|prop| prop.get_name() == "MODE_ID")
+            .unwrap();
+        assert_eq!(mode_id.get_kind(), &PropertyKind::Blob);
+        assert!(!mode_id.is_immutable());
+    }
+
+    #[test]
+    fn test_property_values() {
+        let dev = fixtures::hdmi_device();
+        let buffer = fixtures::framebuffer(&dev);
+
+        let _pipeline = fixtures::pipeline_init(&dev, &buffer)
+            .add_property(&dev.get_planes().unwrap()[0], "rotation", 0x11)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+        let plane = dev.get_planes().unwrap().remove(0);
+
+        assert_eq!(
+            plane.get_property_value("FB_ID").unwrap(),
+            PropertyValue::Object(buffer.get_framebuffer_id().unwrap())
+        );
+        assert_eq!(
+            plane.get_property_value("CRTC_ID").unwrap(),
+            PropertyValue::Object(crtc.get_id())
+        );
+        assert_eq!(
+            plane.get_property_value("type").unwrap(),
+            PropertyValue::Enum(String::from("Primary"))
+        );
+        assert_eq!(
+            plane.get_property_value("rotation").unwrap(),
+            PropertyValue::Bitmask(vec![
+                String::from("rotate-0"),
+                String::from("reflect-x"),
+            ])
+        );
+        assert_eq!(
+            crtc.get_property_value("ACTIVE").unwrap(),
+            PropertyValue::Range(1)
+        );
+        assert!(plane.get_property_value("UNKNOWN").is_err());
+
+        match crtc.get_property_value("MODE_ID").unwrap() {
+            PropertyValue::Blob(id) => assert_ne!(id, 0),
+            value => panic!("Unexpected property value {:?}", value),
+        }
+
+        let properties = plane.get_properties().unwrap();
+        assert_eq!(
+            properties.len(),
+            dev.get_properties(&plane).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_property_cache() {
+        let dev = fixtures::hdmi_output()
+            .set_tracing(true)
+            .into_device()
+            .unwrap();
+        let front = fixtures::framebuffer(&dev);
+        let back = fixtures::framebuffer(&dev);
+
+        let pipeline = fixtures::pipeline_init(&dev, &front).build().unwrap();
+        dev.take_trace().unwrap();
+
+        let pipeline = pipeline.update_buffer(back).unwrap().commit().unwrap();
+        let trace = dev.take_trace().unwrap();
+        let ioctls: Vec<_> =
+            trace.iter().map(|entry| entry.get_ioctl()).collect();
+        assert_eq!(ioctls, vec!["ATOMIC"]);
+
+        dev.invalidate_property_cache();
+        let _pipeline =
+            pipeline.update_buffer(front).unwrap().commit().unwrap();
+        let trace = dev.take_trace().unwrap();
+        assert!(trace.len() > 1);
+    }
 }
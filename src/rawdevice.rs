@@ -1,7 +1,14 @@
 use std::convert::TryInto;
 use std::fs::File;
 use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsFd;
 use std::os::unix::io::AsRawFd;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
 
 use cvt::cvt_r;
 use libc::ioctl;
@@ -9,15 +16,43 @@ use mmap::MapOption;
 use mmap::MemoryMap;
 use vmm_sys_util::ioctl_iowr_nr;
 
+use crate::backend::Backend;
+use crate::error::ioctl_error;
+use crate::error::master_error;
+use crate::error::master_object_error;
+use crate::error::object_error;
 use crate::error::Error;
 use crate::error::Result;
 
 const DRM_IOCTL_BASE: u32 = 'd' as u32;
 
+pub(crate) const DRM_MODE_PROP_RANGE: u32 = 1 << 1;
+pub(crate) const DRM_MODE_PROP_IMMUTABLE: u32 = 1 << 2;
+pub(crate) const DRM_MODE_PROP_ENUM: u32 = 1 << 3;
+pub(crate) const DRM_MODE_PROP_BLOB: u32 = 1 << 4;
+pub(crate) const DRM_MODE_PROP_BITMASK: u32 = 1 << 5;
+pub(crate) const DRM_MODE_PROP_EXTENDED_TYPE: u32 = 0x0000ffc0;
+pub(crate) const DRM_MODE_PROP_OBJECT: u32 = 1 << 6;
+pub(crate) const DRM_MODE_PROP_SIGNED_RANGE: u32 = 2 << 6;
+pub(crate) const DRM_MODE_PROP_ATOMIC: u32 = 0x80000000;
+
+pub(crate) const DRM_MODE_PAGE_FLIP_EVENT: u32 = 0x01;
+pub(crate) const DRM_MODE_PAGE_FLIP_ASYNC: u32 = 0x02;
+pub(crate) const DRM_MODE_ATOMIC_TEST_ONLY: u32 = 0x0100;
+pub(crate) const DRM_MODE_ATOMIC_NONBLOCK: u32 = 0x0200;
+pub(crate) const DRM_MODE_ATOMIC_ALLOW_MODESET: u32 = 0x0400;
+
+pub(crate) const DRM_EVENT_VBLANK: u32 = 0x01;
+pub(crate) const DRM_EVENT_FLIP_COMPLETE: u32 = 0x02;
+pub(crate) const DRM_EVENT_CRTC_SEQUENCE: u32 = 0x03;
+
 #[derive(Clone)]
 #[derive(Copy)]
 #[derive(Debug)]
 #[derive(Default)]
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(C)]
 pub(crate) struct drm_mode_modeinfo {
     pub(crate) clock:       u32,
@@ -37,6 +72,91 @@ pub(crate) struct drm_mode_modeinfo {
     pub(crate) name:        [u8; 32],
 }
 
+impl drm_mode_modeinfo {
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        let size = std::mem::size_of::<drm_mode_modeinfo>();
+
+        let ptr = self as *const drm_mode_modeinfo as *const u8;
+
+        unsafe { std::slice::from_raw_parts(ptr, size) }
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[repr(C)]
+pub(crate) struct drm_version {
+    pub(crate) version_major:      libc::c_int,
+    pub(crate) version_minor:      libc::c_int,
+    pub(crate) version_patchlevel: libc::c_int,
+    pub(crate) name_len:           libc::size_t,
+    pub(crate) name:               usize,
+    pub(crate) date_len:           libc::size_t,
+    pub(crate) date:               usize,
+    pub(crate) desc_len:           libc::size_t,
+    pub(crate) desc:               usize,
+}
+ioctl_iowr_nr!(DRM_IOCTL_VERSION, DRM_IOCTL_BASE, 0x00, drm_version);
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_get_cap {
+    pub(crate) capability: u64,
+    pub(crate) value:      u64,
+}
+ioctl_iowr_nr!(DRM_IOCTL_GET_CAP, DRM_IOCTL_BASE, 0x0c, drm_get_cap);
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_auth {
+    pub(crate) magic: libc::c_uint,
+}
+ioctl_ior_nr!(DRM_IOCTL_GET_MAGIC, DRM_IOCTL_BASE, 0x02, drm_auth);
+ioctl_iow_nr!(DRM_IOCTL_AUTH_MAGIC, DRM_IOCTL_BASE, 0x11, drm_auth);
+
+ioctl_io_nr!(DRM_IOCTL_SET_MASTER, DRM_IOCTL_BASE, 0x1e);
+ioctl_io_nr!(DRM_IOCTL_DROP_MASTER, DRM_IOCTL_BASE, 0x1f);
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_event {
+    pub(crate) type_:  u32,
+    pub(crate) length: u32,
+}
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_event_vblank {
+    pub(crate) base:      drm_event,
+    pub(crate) user_data: u64,
+    pub(crate) tv_sec:    u32,
+    pub(crate) tv_usec:   u32,
+    pub(crate) sequence:  u32,
+    pub(crate) crtc_id:   u32,
+}
+
+impl drm_event_vblank {
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        let size = std::mem::size_of::<drm_event_vblank>();
+
+        let ptr = self as *const drm_event_vblank as *const u8;
+
+        unsafe { std::slice::from_raw_parts(ptr, size) }
+    }
+}
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_event_crtc_sequence {
+    pub(crate) base:      drm_event,
+    pub(crate) user_data: u64,
+    pub(crate) time_ns:   i64,
+    pub(crate) sequence:  u64,
+}
+
 #[repr(C)]
 pub(crate) struct drm_set_client_cap {
     pub(crate) capability: u64,
@@ -73,8 +193,11 @@ ioctl_iowr_nr!(
     drm_mode_card_res
 );
 
+#[derive(Clone)]
 #[derive(Debug)]
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(C)]
 pub(crate) struct drm_mode_crtc {
     pub(crate) set_connectors_ptr: u64,
@@ -90,8 +213,11 @@ pub(crate) struct drm_mode_crtc {
 ioctl_iowr_nr!(DRM_IOCTL_MODE_GETCRTC, DRM_IOCTL_BASE, 0xa1, drm_mode_crtc);
 ioctl_iowr_nr!(DRM_IOCTL_MODE_SETCRTC, DRM_IOCTL_BASE, 0xa2, drm_mode_crtc);
 
+#[derive(Clone)]
 #[derive(Debug)]
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(C)]
 pub(crate) struct drm_mode_get_encoder {
     pub(crate) encoder_id:      u32,
@@ -107,8 +233,11 @@ ioctl_iowr_nr!(
     drm_mode_get_encoder
 );
 
+#[derive(Clone)]
 #[derive(Debug)]
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(C)]
 pub(crate) struct drm_mode_get_connector {
     pub(crate) encoders_ptr:      u64,
@@ -136,8 +265,11 @@ ioctl_iowr_nr!(
     drm_mode_get_connector
 );
 
+#[derive(Clone)]
 #[derive(Debug)]
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(C)]
 pub(crate) struct drm_mode_get_property {
     pub(crate) values_ptr:       u64,
@@ -148,6 +280,17 @@ pub(crate) struct drm_mode_get_property {
     pub(crate) count_values:     u32,
     pub(crate) count_enum_blobs: u32,
 }
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[repr(C)]
+pub(crate) struct drm_mode_property_enum {
+    pub(crate) value: u64,
+    pub(crate) name:  [u8; 32],
+}
+
 ioctl_iowr_nr!(
     DRM_IOCTL_MODE_GETPROPERTY,
     DRM_IOCTL_BASE,
@@ -173,8 +316,12 @@ ioctl_iowr_nr!(
     drm_mode_set_plane
 );
 
+#[derive(Clone)]
+#[derive(Copy)]
 #[derive(Debug)]
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(C)]
 pub(crate) struct drm_mode_create_dumb {
     pub(crate) height: u32,
@@ -232,8 +379,11 @@ ioctl_iowr_nr!(
     drm_mode_get_plane_res
 );
 
+#[derive(Clone)]
 #[derive(Debug)]
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 #[repr(C)]
 pub(crate) struct drm_mode_get_plane {
     pub(crate) plane_id:           u32,
@@ -338,21 +488,191 @@ ioctl_iowr_nr!(
     drm_mode_create_blob
 );
 
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_mode_get_blob {
+    pub(crate) blob_id: u32,
+    pub(crate) length:  u32,
+    pub(crate) data:    u64,
+}
+ioctl_iowr_nr!(
+    DRM_IOCTL_MODE_GETPROPBLOB,
+    DRM_IOCTL_BASE,
+    0xac,
+    drm_mode_get_blob
+);
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_mode_destroy_blob {
+    pub(crate) blob_id: u32,
+}
+ioctl_iowr_nr!(
+    DRM_IOCTL_MODE_DESTROYPROPBLOB,
+    DRM_IOCTL_BASE,
+    0xbe,
+    drm_mode_destroy_blob
+);
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_mode_create_lease {
+    pub(crate) object_ids:   u64,
+    pub(crate) object_count: u32,
+    pub(crate) flags:        u32,
+    pub(crate) lessee_id:    u32,
+    pub(crate) fd:           u32,
+}
+ioctl_iowr_nr!(
+    DRM_IOCTL_MODE_CREATE_LEASE,
+    DRM_IOCTL_BASE,
+    0xc6,
+    drm_mode_create_lease
+);
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_mode_list_lessees {
+    pub(crate) count_lessees: u32,
+    pub(crate) pad:           u32,
+    pub(crate) lessees_ptr:   u64,
+}
+ioctl_iowr_nr!(
+    DRM_IOCTL_MODE_LIST_LESSEES,
+    DRM_IOCTL_BASE,
+    0xc7,
+    drm_mode_list_lessees
+);
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_mode_get_lease {
+    pub(crate) count_objects: u32,
+    pub(crate) pad:           u32,
+    pub(crate) objects_ptr:   u64,
+}
+ioctl_iowr_nr!(
+    DRM_IOCTL_MODE_GET_LEASE,
+    DRM_IOCTL_BASE,
+    0xc8,
+    drm_mode_get_lease
+);
+
+#[derive(Default)]
+#[repr(C)]
+pub(crate) struct drm_mode_revoke_lease {
+    pub(crate) lessee_id: u32,
+}
+ioctl_iowr_nr!(
+    DRM_IOCTL_MODE_REVOKE_LEASE,
+    DRM_IOCTL_BASE,
+    0xc9,
+    drm_mode_revoke_lease
+);
+
 #[derive(Debug)]
 pub(crate) struct RawDevice {
     file: File,
 }
 
 impl RawDevice {
-    pub fn new(path: &str) -> Result<RawDevice> {
-        let file = OpenOptions::new().read(true).write(true).open(path)?;
+    pub fn new(
+        path: &str,
+        writable: bool,
+        flags: libc::c_int,
+    ) -> Result<RawDevice> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(writable)
+            .custom_flags(flags)
+            .open(path)
+            .map_err(ioctl_error("open"))?;
 
         Ok(RawDevice {
             file,
         })
     }
 
-    pub fn allocate_dumb_buffer(
+    pub fn from_fd(fd: OwnedFd) -> RawDevice {
+        RawDevice {
+            file: File::from(fd),
+        }
+    }
+
+    fn get_crtc(&self, id: u32) -> Result<drm_mode_crtc> {
+        let fd = self.file.as_raw_fd();
+
+        let mut crtc: drm_mode_crtc = Default::default();
+        crtc.crtc_id = id;
+
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_GETCRTC(), &mut crtc) })
+            .map_err(object_error("GETCRTC", id))?;
+
+        Ok(crtc)
+    }
+
+    fn get_resources(&self) -> Result<drm_mode_card_res> {
+        let fd = self.file.as_raw_fd();
+
+        let mut resources: drm_mode_card_res = Default::default();
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_GETRESOURCES(), &mut resources)
+        })
+        .map_err(ioctl_error("GETRESOURCES"))?;
+
+        Ok(resources)
+    }
+
+    pub fn is_writable(&self) -> Result<bool> {
+        let fd = self.file.as_raw_fd();
+
+        let flags = cvt_r(|| unsafe { libc::fcntl(fd, libc::F_GETFL) })
+            .map_err(ioctl_error("fcntl"))?;
+
+        Ok(flags & libc::O_ACCMODE != libc::O_RDONLY)
+    }
+
+    pub fn set_cloexec(&self, cloexec: bool) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+
+        let flags = cvt_r(|| unsafe { libc::fcntl(fd, libc::F_GETFD) })
+            .map_err(ioctl_error("fcntl"))?;
+        let flags = if cloexec {
+            flags | libc::FD_CLOEXEC
+        } else {
+            flags & !libc::FD_CLOEXEC
+        };
+
+        cvt_r(|| unsafe { libc::fcntl(fd, libc::F_SETFD, flags) })
+            .map_err(ioctl_error("fcntl"))?;
+
+        Ok(())
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+
+        let flags = cvt_r(|| unsafe { libc::fcntl(fd, libc::F_GETFL) })
+            .map_err(ioctl_error("fcntl"))?;
+        let flags = if nonblocking {
+            flags | libc::O_NONBLOCK
+        } else {
+            flags & !libc::O_NONBLOCK
+        };
+
+        cvt_r(|| unsafe { libc::fcntl(fd, libc::F_SETFL, flags) })
+            .map_err(ioctl_error("fcntl"))?;
+
+        Ok(())
+    }
+}
+
+impl Backend for RawDevice {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+
+    fn allocate_dumb_buffer(
         &self,
         width: usize,
         height: usize,
@@ -366,12 +686,13 @@ impl RawDevice {
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_CREATE_DUMB(), &mut create)
-        })?;
+        })
+        .map_err(ioctl_error("CREATE_DUMB"))?;
 
         Ok(create)
     }
 
-    pub fn add_framebuffer(
+    fn add_framebuffer(
         &self,
         handle: u32,
         width: u32,
@@ -388,51 +709,105 @@ impl RawDevice {
         fb.handles[0] = handle;
         fb.pitches[0] = pitch;
 
-        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_ADDFB2(), &mut fb) })?;
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_ADDFB2(), &mut fb) })
+            .map_err(ioctl_error("ADDFB2"))?;
 
         Ok(fb.fb_id)
     }
 
-    pub fn atomic_commit(
+    fn atomic_commit(
         &self,
         objs_ptr: Vec<u32>,
         count_props_ptr: Vec<u32>,
         props_ptr: Vec<u32>,
         prop_values_ptr: Vec<u64>,
+        flags: u32,
+        user_data: u64,
     ) -> Result<()> {
         let fd = self.file.as_raw_fd();
 
         let atomic: drm_mode_atomic = drm_mode_atomic {
-            flags:           0x0400,
+            flags,
             count_objs:      objs_ptr.len().try_into()?,
             objs_ptr:        objs_ptr.as_ptr() as u64,
             count_props_ptr: count_props_ptr.as_ptr() as u64,
             props_ptr:       props_ptr.as_ptr() as u64,
             prop_values_ptr: prop_values_ptr.as_ptr() as u64,
             reserved:        0,
-            user_data:       0,
+            user_data,
         };
 
-        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_ATOMIC(), &atomic) })?;
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_ATOMIC(), &atomic) })
+            .map_err(master_error("ATOMIC"))?;
 
         Ok(())
     }
 
-    pub fn create_property_blob<T: Sized>(&self, data: &T) -> Result<u32> {
+    fn auth_magic(&self, magic: u32) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+        let auth = drm_auth {
+            magic,
+        };
+
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_AUTH_MAGIC(), &auth) })
+            .map_err(master_error("AUTH_MAGIC"))?;
+
+        Ok(())
+    }
+
+    fn create_lease(
+        &self,
+        objects: &[u32],
+        flags: libc::c_int,
+    ) -> Result<(u32, OwnedFd)> {
+        let fd = self.file.as_raw_fd();
+
+        let mut lease: drm_mode_create_lease = Default::default();
+        lease.object_ids = objects.as_ptr() as u64;
+        lease.object_count = objects.len().try_into()?;
+        lease.flags = flags.try_into()?;
+
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_CREATE_LEASE(), &mut lease)
+        })
+        .map_err(master_error("CREATE_LEASE"))?;
+
+        let lease_fd =
+            unsafe { OwnedFd::from_raw_fd(lease.fd.try_into()?) };
+
+        Ok((lease.lessee_id, lease_fd))
+    }
+
+    fn create_property_blob(&self, data: &[u8]) -> Result<u32> {
         let fd = self.file.as_raw_fd();
 
         let mut blob: drm_mode_create_blob = Default::default();
-        blob.length = std::mem::size_of::<T>().try_into()?;
-        blob.data = (data as *const T) as u64;
+        blob.length = data.len().try_into()?;
+        blob.data = data.as_ptr() as u64;
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_CREATEPROPBLOB(), &mut blob)
-        })?;
+        })
+        .map_err(ioctl_error("CREATEPROPBLOB"))?;
 
         Ok(blob.blob_id)
     }
 
-    pub fn remove_framebuffer(&self, id: u32) {
+    fn destroy_property_blob(&self, id: u32) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+        let destroy = drm_mode_destroy_blob {
+            blob_id: id,
+        };
+
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_DESTROYPROPBLOB(), &destroy)
+        })
+        .map_err(object_error("DESTROYPROPBLOB", id))?;
+
+        Ok(())
+    }
+
+    fn remove_framebuffer(&self, id: u32) {
         let fd = self.file.as_raw_fd();
 
         unsafe {
@@ -440,7 +815,16 @@ impl RawDevice {
         }
     }
 
-    pub fn destroy_dumb_buffer(&self, handle: u32) {
+    fn drop_master(&self) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_DROP_MASTER(), 0) })
+            .map_err(ioctl_error("DROP_MASTER"))?;
+
+        Ok(())
+    }
+
+    fn destroy_dumb_buffer(&self, handle: u32) {
         let fd = self.file.as_raw_fd();
         let destroy = drm_mode_destroy_dumb {
             handle,
@@ -451,7 +835,49 @@ impl RawDevice {
         });
     }
 
-    pub fn get_encoder(&self, id: u32) -> Result<drm_mode_get_encoder> {
+    fn get_capability(&self, cap: u64) -> Result<u64> {
+        let fd = self.file.as_raw_fd();
+
+        let mut get: drm_get_cap = Default::default();
+        get.capability = cap;
+
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_GET_CAP(), &mut get) })
+            .map_err(ioctl_error("GET_CAP"))?;
+
+        Ok(get.value)
+    }
+
+    fn get_driver_version(
+        &self,
+        name: &mut Vec<u8>,
+        date: &mut Vec<u8>,
+        desc: &mut Vec<u8>,
+    ) -> Result<drm_version> {
+        let fd = self.file.as_raw_fd();
+
+        let mut count: drm_version = Default::default();
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_VERSION(), &mut count) })
+            .map_err(ioctl_error("VERSION"))?;
+
+        name.resize(count.name_len, 0);
+        date.resize(count.date_len, 0);
+        desc.resize(count.desc_len, 0);
+
+        let mut version: drm_version = Default::default();
+        version.name_len = name.len();
+        version.name = name.as_mut_ptr() as usize;
+        version.date_len = date.len();
+        version.date = date.as_mut_ptr() as usize;
+        version.desc_len = desc.len();
+        version.desc = desc.as_mut_ptr() as usize;
+
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_VERSION(), &mut version) })
+            .map_err(ioctl_error("VERSION"))?;
+
+        Ok(version)
+    }
+
+    fn get_encoder(&self, id: u32) -> Result<drm_mode_get_encoder> {
         let fd = self.file.as_raw_fd();
 
         let mut encoder: drm_mode_get_encoder = Default::default();
@@ -459,12 +885,13 @@ impl RawDevice {
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_GETENCODER(), &mut encoder)
-        })?;
+        })
+        .map_err(object_error("GETENCODER", id))?;
 
         Ok(encoder)
     }
 
-    pub fn get_connector(
+    fn get_connector(
         &self,
         id: u32,
         modes: Option<&mut Vec<drm_mode_modeinfo>>,
@@ -482,7 +909,8 @@ impl RawDevice {
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_GETCONNECTOR(), &mut count)
-        })?;
+        })
+        .map_err(object_error("GETCONNECTOR", id))?;
 
         if modes.is_none() && encoders.is_none() && properties.is_none() {
             return Ok(count);
@@ -514,31 +942,13 @@ impl RawDevice {
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_GETCONNECTOR(), &mut conn)
-        })?;
+        })
+        .map_err(object_error("GETCONNECTOR", id))?;
 
         Ok(conn)
     }
 
-    pub fn get_connector_modes(
-        &self,
-        id: u32,
-    ) -> Result<Vec<drm_mode_modeinfo>> {
-        let mut mod_info = Vec::new();
-
-        let _ = self.get_connector(id, Some(&mut mod_info), None, None)?;
-
-        Ok(mod_info)
-    }
-
-    pub fn get_connector_encoders(&self, id: u32) -> Result<Vec<u32>> {
-        let mut enc_ids = Vec::new();
-
-        let _ = self.get_connector(id, None, Some(&mut enc_ids), None)?;
-
-        Ok(enc_ids)
-    }
-
-    pub fn get_connectors(&self) -> Result<Vec<drm_mode_get_connector>> {
+    fn get_connectors(&self) -> Result<Vec<drm_mode_get_connector>> {
         let fd = self.file.as_raw_fd();
         let count = self.get_resources()?;
 
@@ -552,7 +962,8 @@ impl RawDevice {
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_GETRESOURCES(), &mut resources)
-        })?;
+        })
+        .map_err(ioctl_error("GETRESOURCES"))?;
 
         let mut connectors = Vec::with_capacity(connector_id.len());
         for id in connector_id {
@@ -562,18 +973,7 @@ impl RawDevice {
         Ok(connectors)
     }
 
-    fn get_crtc(&self, id: u32) -> Result<drm_mode_crtc> {
-        let fd = self.file.as_raw_fd();
-
-        let mut crtc: drm_mode_crtc = Default::default();
-        crtc.crtc_id = id;
-
-        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_GETCRTC(), &mut crtc) })?;
-
-        Ok(crtc)
-    }
-
-    pub fn get_crtcs(&self) -> Result<Vec<drm_mode_crtc>> {
+    fn get_crtcs(&self) -> Result<Vec<drm_mode_crtc>> {
         let fd = self.file.as_raw_fd();
         let count = self.get_resources()?;
 
@@ -587,7 +987,8 @@ impl RawDevice {
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_GETRESOURCES(), &mut resources)
-        })?;
+        })
+        .map_err(ioctl_error("GETRESOURCES"))?;
 
         let mut crtcs = Vec::with_capacity(crtc_id.len());
         for id in crtc_id {
@@ -597,24 +998,26 @@ impl RawDevice {
         Ok(crtcs)
     }
 
-    pub(crate) fn get_plane(&self, id: u32) -> Result<drm_mode_get_plane> {
+    fn get_plane(&self, id: u32) -> Result<drm_mode_get_plane> {
         let fd = self.file.as_raw_fd();
 
         let mut plane: drm_mode_get_plane = Default::default();
         plane.plane_id = id;
 
-        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_GETPLANE(), &mut plane) })?;
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_GETPLANE(), &mut plane) })
+            .map_err(object_error("GETPLANE", id))?;
 
         Ok(plane)
     }
 
-    pub fn get_planes(&self) -> Result<Vec<drm_mode_get_plane>> {
+    fn get_planes(&self) -> Result<Vec<drm_mode_get_plane>> {
         let fd = self.file.as_raw_fd();
 
         let mut count: drm_mode_get_plane_res = Default::default();
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_GETPLANERESOURCES(), &mut count)
-        })?;
+        })
+        .map_err(ioctl_error("GETPLANERESOURCES"))?;
 
         let mut resources: drm_mode_get_plane_res = Default::default();
         resources.count_planes = count.count_planes;
@@ -626,7 +1029,8 @@ impl RawDevice {
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_GETPLANERESOURCES(), &mut resources)
-        })?;
+        })
+        .map_err(ioctl_error("GETPLANERESOURCES"))?;
 
         let mut planes = Vec::with_capacity(plane_id.len());
         for id in plane_id {
@@ -636,7 +1040,46 @@ impl RawDevice {
         Ok(planes)
     }
 
-    pub fn get_property(&self, id: u32) -> Result<drm_mode_get_property> {
+    fn get_lease(&self) -> Result<Vec<u32>> {
+        let fd = self.file.as_raw_fd();
+
+        let mut count: drm_mode_get_lease = Default::default();
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_GET_LEASE(), &mut count)
+        })
+        .map_err(ioctl_error("GET_LEASE"))?;
+
+        let mut objects = vec![0u32; count.count_objects as usize];
+
+        let mut lease: drm_mode_get_lease = Default::default();
+        lease.count_objects = count.count_objects;
+        lease.objects_ptr = objects.as_mut_ptr() as u64;
+
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_GET_LEASE(), &mut lease)
+        })
+        .map_err(ioctl_error("GET_LEASE"))?;
+
+        objects.truncate(lease.count_objects as usize);
+        Ok(objects)
+    }
+
+    fn get_magic(&self) -> Result<u32> {
+        let fd = self.file.as_raw_fd();
+
+        let mut auth: drm_auth = Default::default();
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_GET_MAGIC(), &mut auth) })
+            .map_err(ioctl_error("GET_MAGIC"))?;
+
+        Ok(auth.magic)
+    }
+
+    fn get_property(
+        &self,
+        id: u32,
+        values: Option<&mut Vec<u64>>,
+        enums: Option<&mut Vec<drm_mode_property_enum>>,
+    ) -> Result<drm_mode_get_property> {
         let fd = self.file.as_raw_fd();
 
         let mut count: drm_mode_get_property = Default::default();
@@ -644,16 +1087,44 @@ impl RawDevice {
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_GETPROPERTY(), &mut count)
-        })?;
+        })
+        .map_err(object_error("GETPROPERTY", id))?;
+
+        if values.is_none() && enums.is_none() {
+            return Ok(count);
+        }
+
+        let mut prop: drm_mode_get_property = Default::default();
+        prop.prop_id = id;
+
+        if let Some(values) = values {
+            values.resize(count.count_values as usize, 0);
+            prop.count_values = count.count_values;
+            prop.values_ptr = values.as_mut_ptr() as u64;
+        }
+
+        if let Some(enums) = enums {
+            enums.resize_with(
+                count.count_enum_blobs as usize,
+                Default::default,
+            );
+            prop.count_enum_blobs = count.count_enum_blobs;
+            prop.enum_blob_ptr = enums.as_mut_ptr() as u64;
+        }
 
-        Ok(count)
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_GETPROPERTY(), &mut prop)
+        })
+        .map_err(object_error("GETPROPERTY", id))?;
+
+        Ok(prop)
     }
 
-    pub fn get_properties(
+    fn get_properties(
         &self,
         object_type: u32,
         object_id: u32,
-    ) -> Result<Vec<u32>> {
+    ) -> Result<Vec<(u32, u64)>> {
         let fd = self.file.as_raw_fd();
 
         let mut count: drm_mode_obj_get_properties = Default::default();
@@ -661,7 +1132,8 @@ impl RawDevice {
         count.obj_id = object_id;
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_OBJ_GETPROPERTIES(), &mut count)
-        })?;
+        })
+        .map_err(object_error("OBJ_GETPROPERTIES", object_id))?;
 
         let mut properties: drm_mode_obj_get_properties = Default::default();
         properties.obj_type = object_type;
@@ -680,23 +1152,80 @@ impl RawDevice {
 
         cvt_r(|| unsafe {
             ioctl(fd, DRM_IOCTL_MODE_OBJ_GETPROPERTIES(), &mut properties)
-        })?;
+        })
+        .map_err(object_error("OBJ_GETPROPERTIES", object_id))?;
 
-        Ok(prop_ids)
+        Ok(prop_ids.into_iter().zip(prop_values).collect())
     }
 
-    pub fn get_resources(&self) -> Result<drm_mode_card_res> {
+    fn get_property_blob(&self, id: u32) -> Result<Vec<u8>> {
         let fd = self.file.as_raw_fd();
 
-        let mut resources: drm_mode_card_res = Default::default();
+        let mut count: drm_mode_get_blob = Default::default();
+        count.blob_id = id;
+
         cvt_r(|| unsafe {
-            ioctl(fd, DRM_IOCTL_MODE_GETRESOURCES(), &mut resources)
-        })?;
+            ioctl(fd, DRM_IOCTL_MODE_GETPROPBLOB(), &mut count)
+        })
+        .map_err(object_error("GETPROPBLOB", id))?;
 
-        Ok(resources)
+        let mut data = vec![0u8; count.length as usize];
+
+        let mut blob: drm_mode_get_blob = Default::default();
+        blob.blob_id = id;
+        blob.length = count.length;
+        blob.data = data.as_mut_ptr() as u64;
+
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_GETPROPBLOB(), &mut blob)
+        })
+        .map_err(object_error("GETPROPBLOB", id))?;
+
+        Ok(data)
+    }
+
+    fn is_master(&self) -> Result<bool> {
+        let fd = self.file.as_raw_fd();
+
+        // Authenticating the magic 0 can't succeed, but the master check
+        // is done first, so we'll get EACCES if we're not master and
+        // EINVAL otherwise.
+        let auth: drm_auth = Default::default();
+        let ret = cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_AUTH_MAGIC(), &auth) });
+
+        match ret {
+            Ok(_) => Ok(true),
+            Err(err) if err.raw_os_error() == Some(libc::EACCES) => Ok(false),
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => Ok(true),
+            Err(err) => Err(ioctl_error("AUTH_MAGIC")(err)),
+        }
+    }
+
+    fn list_lessees(&self) -> Result<Vec<u32>> {
+        let fd = self.file.as_raw_fd();
+
+        let mut count: drm_mode_list_lessees = Default::default();
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_LIST_LESSEES(), &mut count)
+        })
+        .map_err(master_error("LIST_LESSEES"))?;
+
+        let mut lessees = vec![0u32; count.count_lessees as usize];
+
+        let mut list: drm_mode_list_lessees = Default::default();
+        list.count_lessees = count.count_lessees;
+        list.lessees_ptr = lessees.as_mut_ptr() as u64;
+
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_LIST_LESSEES(), &mut list)
+        })
+        .map_err(master_error("LIST_LESSEES"))?;
+
+        lessees.truncate(list.count_lessees as usize);
+        Ok(lessees)
     }
 
-    pub fn map_dumb_buffer(
+    fn map_dumb_buffer(
         &self,
         handle: u32,
         size: usize,
@@ -706,7 +1235,8 @@ impl RawDevice {
         let mut map: drm_mode_map_dumb = Default::default();
         map.handle = handle;
 
-        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_MAP_DUMB(), &mut map) })?;
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_MAP_DUMB(), &mut map) })
+            .map_err(ioctl_error("MAP_DUMB"))?;
 
         let mapping: MemoryMap = MemoryMap::new(
             size,
@@ -722,7 +1252,30 @@ impl RawDevice {
         Ok(mapping)
     }
 
-    pub fn set_crtc(
+    fn read_events(&self, buf: &mut [u8]) -> Result<usize> {
+        match (&self.file).read(buf) {
+            Ok(len) => Ok(len),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(0),
+            Err(err) => Err(ioctl_error("read")(err)),
+        }
+    }
+
+    fn revoke_lease(&self, lessee_id: u32) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+
+        let mut revoke = drm_mode_revoke_lease {
+            lessee_id,
+        };
+
+        cvt_r(|| unsafe {
+            ioctl(fd, DRM_IOCTL_MODE_REVOKE_LEASE(), &mut revoke)
+        })
+        .map_err(master_error("REVOKE_LEASE"))?;
+
+        Ok(())
+    }
+
+    fn set_crtc(
         &self,
         fb_id: u32,
         crtc_id: u32,
@@ -752,24 +1305,35 @@ impl RawDevice {
             crtc.mode_valid = 1;
         }
 
-        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_SETCRTC(), &crtc) })?;
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_SETCRTC(), &crtc) })
+            .map_err(master_object_error("SETCRTC", crtc_id))?;
 
         Ok(())
     }
 
-    pub fn set_client_capability(&self, cap: u64) -> Result<()> {
+    fn set_client_capability(&self, cap: u64) -> Result<()> {
         let fd = self.file.as_raw_fd();
         let caps = drm_set_client_cap {
             capability: cap,
             value:      1,
         };
 
-        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_SET_CLIENT_CAP(), &caps) })?;
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_SET_CLIENT_CAP(), &caps) })
+            .map_err(ioctl_error("SET_CLIENT_CAP"))?;
+
+        Ok(())
+    }
+
+    fn set_master(&self) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_SET_MASTER(), 0) })
+            .map_err(ioctl_error("SET_MASTER"))?;
 
         Ok(())
     }
 
-    pub fn set_plane(
+    fn set_plane(
         &self,
         fb_id: u32,
         plane_id: u32,
@@ -788,7 +1352,8 @@ impl RawDevice {
         s.src_w = w << 16;
         s.src_h = h << 16;
 
-        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_SETPLANE(), &s) })?;
+        cvt_r(|| unsafe { ioctl(fd, DRM_IOCTL_MODE_SETPLANE(), &s) })
+            .map_err(master_object_error("SETPLANE", plane_id))?;
 
         Ok(())
     }
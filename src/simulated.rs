@@ -0,0 +1,1749 @@
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use cvt::cvt;
+use mmap::MapOption;
+use mmap::MemoryMap;
+
+use crate::backend::Backend;
+use crate::connector::ConnectorStatus;
+use crate::connector::ConnectorType;
+use crate::device::ClientCapability;
+use crate::device::Device;
+use crate::error::Error;
+use crate::error::Result;
+use crate::object::ObjectType;
+use crate::rawdevice::drm_event;
+use crate::rawdevice::drm_event_vblank;
+use crate::rawdevice::drm_mode_create_dumb;
+use crate::rawdevice::drm_mode_crtc;
+use crate::rawdevice::drm_mode_get_connector;
+use crate::rawdevice::drm_mode_get_encoder;
+use crate::rawdevice::drm_mode_get_plane;
+use crate::rawdevice::drm_mode_get_property;
+use crate::rawdevice::drm_mode_modeinfo;
+use crate::rawdevice::drm_mode_property_enum;
+use crate::rawdevice::drm_version;
+use crate::rawdevice::DRM_EVENT_FLIP_COMPLETE;
+use crate::rawdevice::DRM_MODE_ATOMIC_ALLOW_MODESET;
+use crate::rawdevice::DRM_MODE_ATOMIC_NONBLOCK;
+use crate::rawdevice::DRM_MODE_ATOMIC_TEST_ONLY;
+use crate::rawdevice::DRM_MODE_PAGE_FLIP_EVENT;
+use crate::rawdevice::DRM_MODE_PROP_ATOMIC;
+use crate::rawdevice::DRM_MODE_PROP_BITMASK;
+use crate::rawdevice::DRM_MODE_PROP_BLOB;
+use crate::rawdevice::DRM_MODE_PROP_ENUM;
+use crate::rawdevice::DRM_MODE_PROP_EXTENDED_TYPE;
+use crate::rawdevice::DRM_MODE_PROP_IMMUTABLE;
+use crate::rawdevice::DRM_MODE_PROP_OBJECT;
+use crate::rawdevice::DRM_MODE_PROP_RANGE;
+use crate::rawdevice::DRM_MODE_PROP_SIGNED_RANGE;
+
+const DRIVER_NAME: &str = "doremi-sim";
+const DRIVER_DATE: &str = "20200101";
+const DRIVER_DESC: &str = "Simulated KMS device";
+
+const PLANE_TYPE_OVERLAY: u64 = 0;
+const PLANE_TYPE_PRIMARY: u64 = 1;
+
+const ENCODER_TYPE_DAC: u32 = 1;
+const ENCODER_TYPE_TMDS: u32 = 2;
+const ENCODER_TYPE_LVDS: u32 = 3;
+const ENCODER_TYPE_VIRTUAL: u32 = 5;
+const ENCODER_TYPE_DSI: u32 = 6;
+
+const FORMAT_XRGB8888: u32 = 0x34325258;
+const FORMAT_RGB888: u32 = 0x34324752;
+
+fn errno(operation: &str, err: i32) -> Error {
+    Error::from_errno(operation, err, None)
+}
+
+// Drivers turn down a configuration they can't display with EINVAL
+fn rejected(operation: &str) -> Error {
+    errno(operation, libc::EINVAL)
+}
+
+fn object_mut<'a>(
+    operation: &str,
+    objects: &'a mut BTreeMap<u32, SimulatedObject>,
+    id: u32,
+) -> Result<&'a mut SimulatedObject> {
+    objects
+        .get_mut(&id)
+        .ok_or_else(|| Error::from_errno(operation, libc::ENOENT, Some(id)))
+}
+
+fn fill_name(name: &str) -> [u8; 32] {
+    let mut ret = [0; 32];
+    let len = name.len().min(ret.len() - 1);
+
+    ret[..len].copy_from_slice(&name.as_bytes()[..len]);
+    ret
+}
+
+fn simulated_mode(width: u16, height: u16, refresh: u32) -> drm_mode_modeinfo {
+    let htotal = width + 280;
+    let vtotal = height + 45;
+    let clock = (htotal as u32) * (vtotal as u32) * refresh / 1000;
+
+    drm_mode_modeinfo {
+        clock,
+        hdisplay: width,
+        hsync_start: width + 88,
+        hsync_end: width + 132,
+        htotal,
+        hskew: 0,
+        vdisplay: height,
+        vsync_start: height + 4,
+        vsync_end: height + 9,
+        vtotal,
+        vscan: 0,
+        vrefresh: refresh,
+        flags: 0,
+        // DRM_MODE_TYPE_PREFERRED | DRM_MODE_TYPE_DRIVER
+        type_: (1 << 3) | (1 << 6),
+        name: fill_name(&format!("{}x{}", width, height)),
+    }
+}
+
+#[derive(Debug)]
+struct SimulatedProperty {
+    id:     u32,
+    name:   String,
+    flags:  u32,
+    values: Vec<u64>,
+    enums:  Vec<(u64, String)>,
+}
+
+impl SimulatedProperty {
+    fn is_valid(&self, state: &SimulatedState, value: u64) -> bool {
+        let ext_type = self.flags & DRM_MODE_PROP_EXTENDED_TYPE;
+
+        if self.flags & DRM_MODE_PROP_RANGE != 0 {
+            value >= self.values[0] && value <= self.values[1]
+        } else if ext_type == DRM_MODE_PROP_SIGNED_RANGE {
+            let value = value as i64;
+
+            value >= self.values[0] as i64 && value <= self.values[1] as i64
+        } else if self.flags & DRM_MODE_PROP_ENUM != 0 {
+            self.enums.iter().any(|(val, _)| *val == value)
+        } else if self.flags & DRM_MODE_PROP_BITMASK != 0 {
+            let mask =
+                self.enums.iter().fold(0, |mask, (bit, _)| mask | 1 << bit);
+
+            value & !mask == 0
+        } else if self.flags & DRM_MODE_PROP_BLOB != 0 {
+            value == 0 || state.blobs.contains_key(&(value as u32))
+        } else if ext_type == DRM_MODE_PROP_OBJECT {
+            value == 0 ||
+                state
+                    .objects
+                    .get(&(value as u32))
+                    .is_some_and(|obj| obj.type_ as u64 == self.values[0])
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+struct SimulatedObject {
+    type_:      u32,
+    properties: Vec<(u32, u64)>,
+}
+
+impl SimulatedObject {
+    fn get(&self, prop_id: u32) -> u64 {
+        self.properties
+            .iter()
+            .find(|(id, _)| *id == prop_id)
+            .map_or(0, |(_, value)| *value)
+    }
+
+    fn set(
+        &mut self,
+        operation: &str,
+        prop_id: u32,
+        value: u64,
+    ) -> Result<()> {
+        let prop = self
+            .properties
+            .iter_mut()
+            .find(|(id, _)| *id == prop_id)
+            .ok_or_else(|| rejected(operation))?;
+
+        prop.1 = value;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct SimulatedConnector {
+    id:        u32,
+    type_:     u32,
+    type_id:   u32,
+    status:    u32,
+    encoders:  Vec<u32>,
+    modes:     Vec<drm_mode_modeinfo>,
+    mm_width:  u32,
+    mm_height: u32,
+}
+
+#[derive(Debug)]
+struct SimulatedEncoder {
+    id:             u32,
+    type_:          u32,
+    possible_crtcs: u32,
+}
+
+#[derive(Debug)]
+struct SimulatedPlane {
+    id:             u32,
+    possible_crtcs: u32,
+    formats:        Vec<u32>,
+}
+
+#[derive(Debug)]
+struct SimulatedFramebuffer {
+    width:  u32,
+    height: u32,
+}
+
+#[derive(Debug)]
+#[derive(Default)]
+struct SimulatedState {
+    next_id:      u32,
+    next_handle:  u32,
+    next_magic:   u32,
+    master:       bool,
+    magics:       Vec<u32>,
+    client_caps:  Vec<u64>,
+    properties:   Vec<SimulatedProperty>,
+    objects:      BTreeMap<u32, SimulatedObject>,
+    connectors:   Vec<SimulatedConnector>,
+    encoders:     Vec<SimulatedEncoder>,
+    crtcs:        Vec<u32>,
+    planes:       Vec<SimulatedPlane>,
+    blobs:        BTreeMap<u32, Vec<u8>>,
+    dumbs:        BTreeMap<u32, drm_mode_create_dumb>,
+    framebuffers: BTreeMap<u32, SimulatedFramebuffer>,
+    events:       VecDeque<Vec<u8>>,
+    sequence:     u32,
+}
+
+impl SimulatedState {
+    fn new() -> Self {
+        let mut state = SimulatedState {
+            next_id: 1,
+            next_handle: 1,
+            next_magic: 1,
+            master: true,
+            ..Default::default()
+        };
+
+        let crtc = ObjectType::Crtc as u64;
+        let fb = ObjectType::Fb as u64;
+
+        let range = DRM_MODE_PROP_ATOMIC | DRM_MODE_PROP_RANGE;
+        let object = DRM_MODE_PROP_ATOMIC | DRM_MODE_PROP_OBJECT;
+        let signed = DRM_MODE_PROP_ATOMIC | DRM_MODE_PROP_SIGNED_RANGE;
+        let blob = DRM_MODE_PROP_ATOMIC | DRM_MODE_PROP_BLOB;
+        let u32_max = u32::MAX as u64;
+        let i32_min = i32::MIN as i64 as u64;
+        let i32_max = i32::MAX as u64;
+
+        state.add_property(
+            ObjectType::Connector,
+            "EDID",
+            DRM_MODE_PROP_BLOB | DRM_MODE_PROP_IMMUTABLE,
+            &[],
+            &[],
+        );
+        state.add_property(
+            ObjectType::Connector,
+            "DPMS",
+            DRM_MODE_PROP_ENUM,
+            &[],
+            &["On", "Standby", "Suspend", "Off"],
+        );
+        state.add_property(
+            ObjectType::Connector,
+            "CRTC_ID",
+            object,
+            &[crtc],
+            &[],
+        );
+
+        state.add_property(ObjectType::Crtc, "ACTIVE", range, &[0, 1], &[]);
+        state.add_property(ObjectType::Crtc, "MODE_ID", blob, &[], &[]);
+
+        state.add_property(
+            ObjectType::Plane,
+            "type",
+            DRM_MODE_PROP_ENUM | DRM_MODE_PROP_IMMUTABLE,
+            &[],
+            &["Overlay", "Primary", "Cursor"],
+        );
+        state.add_property(ObjectType::Plane, "FB_ID", object, &[fb], &[]);
+        state.add_property(ObjectType::Plane, "CRTC_ID", object, &[crtc], &[]);
+
+        for name in &["SRC_X", "SRC_Y", "SRC_W", "SRC_H"] {
+            let values = [0, u32_max];
+
+            state.add_property(ObjectType::Plane, name, range, &values, &[]);
+        }
+
+        for name in &["CRTC_X", "CRTC_Y"] {
+            let values = [i32_min, i32_max];
+
+            state.add_property(ObjectType::Plane, name, signed, &values, &[]);
+        }
+
+        for name in &["CRTC_W", "CRTC_H"] {
+            let values = [0, i32_max];
+
+            state.add_property(ObjectType::Plane, name, range, &values, &[]);
+        }
+
+        state.add_property(
+            ObjectType::Plane,
+            "rotation",
+            DRM_MODE_PROP_BITMASK,
+            &[],
+            &[
+                "rotate-0",
+                "rotate-90",
+                "rotate-180",
+                "rotate-270",
+                "reflect-x",
+                "reflect-y",
+            ],
+        );
+        state.add_property(
+            ObjectType::Plane,
+            "alpha",
+            DRM_MODE_PROP_RANGE,
+            &[0, 0xffff],
+            &[],
+        );
+        state.add_property(
+            ObjectType::Plane,
+            "pixel blend mode",
+            DRM_MODE_PROP_ENUM,
+            &[],
+            &["None", "Pre-multiplied", "Coverage"],
+        );
+
+        state
+    }
+
+    // Like the kernel, properties are shared by all the objects of a given
+    // type. We prefix the name with the type to tell them apart.
+    fn add_property(
+        &mut self,
+        type_: ObjectType,
+        name: &str,
+        flags: u32,
+        values: &[u64],
+        enums: &[&str],
+    ) {
+        let id = self.allocate_id();
+
+        self.properties.push(SimulatedProperty {
+            id,
+            name: format!("{}:{}", type_ as u32, name),
+            flags,
+            values: values.to_vec(),
+            enums: enums
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| (idx as u64, name.to_string()))
+                .collect(),
+        });
+    }
+
+    fn allocate_id(&mut self) -> u32 {
+        let id = self.next_id;
+
+        self.next_id += 1;
+        id
+    }
+
+    fn add_object(
+        &mut self,
+        type_: ObjectType,
+        defaults: &[(&str, u64)],
+    ) -> u32 {
+        let type_ = type_ as u32;
+        let id = self.allocate_id();
+        let prefix = format!("{}:", type_);
+
+        let properties = self
+            .properties
+            .iter()
+            .filter(|prop| prop.name.starts_with(&prefix))
+            .map(|prop| {
+                let name = &prop.name[prefix.len()..];
+                let value = defaults
+                    .iter()
+                    .find(|(default, _)| *default == name)
+                    .map_or(0, |(_, value)| *value);
+
+                (prop.id, value)
+            })
+            .collect();
+
+        self.objects.insert(id, SimulatedObject {
+            type_,
+            properties,
+        });
+
+        id
+    }
+
+    fn property_id(&self, type_: ObjectType, name: &str) -> u32 {
+        let name = format!("{}:{}", type_ as u32, name);
+
+        self.properties
+            .iter()
+            .find(|prop| prop.name == name)
+            .map(|prop| prop.id)
+            .expect("Unknown simulated property")
+    }
+
+    fn get_object(
+        &self,
+        operation: &str,
+        id: u32,
+        type_: ObjectType,
+    ) -> Result<&SimulatedObject> {
+        let type_ = type_ as u32;
+
+        self.objects
+            .get(&id)
+            .filter(|obj| obj.type_ == type_)
+            .ok_or_else(|| Error::from_errno(operation, libc::ENOENT, Some(id)))
+    }
+
+    fn crtc_index(&self, id: u32) -> Option<usize> {
+        self.crtcs.iter().position(|crtc| *crtc == id)
+    }
+
+    fn check(
+        &self,
+        operation: &str,
+        objects: &BTreeMap<u32, SimulatedObject>,
+    ) -> Result<()> {
+        let get = |id: u32, type_: ObjectType, name: &str| {
+            let prop = self.property_id(type_, name);
+
+            objects.get(&id).map_or(0, |obj| obj.get(prop))
+        };
+
+        for plane in &self.planes {
+            let fb_id = get(plane.id, ObjectType::Plane, "FB_ID") as u32;
+            let crtc_id = get(plane.id, ObjectType::Plane, "CRTC_ID") as u32;
+
+            if (fb_id == 0) != (crtc_id == 0) {
+                return Err(rejected(operation));
+            }
+
+            if fb_id == 0 {
+                continue;
+            }
+
+            let index = self
+                .crtc_index(crtc_id)
+                .ok_or_else(|| rejected(operation))?;
+            if plane.possible_crtcs & (1 << index) == 0 {
+                return Err(rejected(operation));
+            }
+
+            let fb = self
+                .framebuffers
+                .get(&fb_id)
+                .ok_or_else(|| rejected(operation))?;
+
+            let src_x = get(plane.id, ObjectType::Plane, "SRC_X");
+            let src_y = get(plane.id, ObjectType::Plane, "SRC_Y");
+            let src_w = get(plane.id, ObjectType::Plane, "SRC_W");
+            let src_h = get(plane.id, ObjectType::Plane, "SRC_H");
+            let crtc_w = get(plane.id, ObjectType::Plane, "CRTC_W");
+            let crtc_h = get(plane.id, ObjectType::Plane, "CRTC_H");
+
+            if src_x + src_w > (fb.width as u64) << 16 ||
+                src_y + src_h > (fb.height as u64) << 16 ||
+                crtc_w == 0 ||
+                crtc_h == 0
+            {
+                return Err(rejected(operation));
+            }
+        }
+
+        for connector in &self.connectors {
+            let crtc_id =
+                get(connector.id, ObjectType::Connector, "CRTC_ID") as u32;
+
+            if crtc_id == 0 {
+                continue;
+            }
+
+            let index = self
+                .crtc_index(crtc_id)
+                .ok_or_else(|| rejected(operation))?;
+            let routable = connector.encoders.iter().any(|enc_id| {
+                self.encoders
+                    .iter()
+                    .find(|encoder| encoder.id == *enc_id)
+                    .is_some_and(|encoder| {
+                        encoder.possible_crtcs & (1 << index) != 0
+                    })
+            });
+
+            if !routable {
+                return Err(rejected(operation));
+            }
+        }
+
+        for crtc in &self.crtcs {
+            let active = get(*crtc, ObjectType::Crtc, "ACTIVE");
+            let mode_id = get(*crtc, ObjectType::Crtc, "MODE_ID") as u32;
+
+            if active != 0 && mode_id == 0 {
+                return Err(rejected(operation));
+            }
+
+            if mode_id == 0 {
+                continue;
+            }
+
+            let blob = self
+                .blobs
+                .get(&mode_id)
+                .ok_or_else(|| rejected(operation))?;
+            if blob.len() != std::mem::size_of::<drm_mode_modeinfo>() {
+                return Err(rejected(operation));
+            }
+
+            let routed = self.connectors.iter().any(|connector| {
+                let id = get(connector.id, ObjectType::Connector, "CRTC_ID");
+
+                id == *crtc as u64
+            });
+
+            if !routed {
+                return Err(rejected(operation));
+            }
+        }
+
+        Ok(())
+    }
+
+    // The CRTCs a commit touches, either directly or through the planes
+    // and connectors attached to them before or after the commit.
+    fn affected_crtcs(
+        &self,
+        ids: &[u32],
+        objects: &BTreeMap<u32, SimulatedObject>,
+    ) -> Vec<u32> {
+        let plane_crtc = self.property_id(ObjectType::Plane, "CRTC_ID");
+        let conn_crtc = self.property_id(ObjectType::Connector, "CRTC_ID");
+        let mut crtcs = Vec::new();
+
+        for id in ids {
+            if self.crtc_index(*id).is_some() {
+                crtcs.push(*id);
+                continue;
+            }
+
+            for objs in &[&self.objects, objects] {
+                let crtc = objs.get(id).map_or(0, |obj| {
+                    obj.get(plane_crtc) | obj.get(conn_crtc)
+                }) as u32;
+
+                if crtc != 0 {
+                    crtcs.push(crtc);
+                }
+            }
+        }
+
+        crtcs.sort_unstable();
+        crtcs.dedup();
+        crtcs
+    }
+
+    fn needs_modeset(&self, objects: &BTreeMap<u32, SimulatedObject>) -> bool {
+        let get = |objs: &BTreeMap<u32, SimulatedObject>, id, prop| {
+            objs.get(&id).map_or(0, |obj: &SimulatedObject| obj.get(prop))
+        };
+
+        let active = self.property_id(ObjectType::Crtc, "ACTIVE");
+        let mode_id = self.property_id(ObjectType::Crtc, "MODE_ID");
+        let crtc_id = self.property_id(ObjectType::Connector, "CRTC_ID");
+
+        let crtc_changed = self.crtcs.iter().any(|crtc| {
+            let old_mode = get(&self.objects, *crtc, mode_id) as u32;
+            let new_mode = get(objects, *crtc, mode_id) as u32;
+
+            get(&self.objects, *crtc, active) != get(objects, *crtc, active) ||
+                self.blobs.get(&old_mode) != self.blobs.get(&new_mode)
+        });
+
+        let routing_changed = self.connectors.iter().any(|connector| {
+            get(&self.objects, connector.id, crtc_id) !=
+                get(objects, connector.id, crtc_id)
+        });
+
+        crtc_changed || routing_changed
+    }
+
+    fn queue_flip_events(&mut self, crtcs: &[u32], user_data: u64) {
+        let mut now = libc::timespec {
+            tv_sec:  0,
+            tv_nsec: 0,
+        };
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+
+        self.sequence += 1;
+
+        for crtc in crtcs {
+            let event = drm_event_vblank {
+                base: drm_event {
+                    type_:  DRM_EVENT_FLIP_COMPLETE,
+                    length: std::mem::size_of::<drm_event_vblank>() as u32,
+                },
+                user_data,
+                tv_sec: now.tv_sec as u32,
+                tv_usec: (now.tv_nsec / 1000) as u32,
+                sequence: self.sequence,
+                crtc_id: *crtc,
+            };
+
+            self.events.push_back(event.as_bytes().to_vec());
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SimulatedDevice {
+    state:   SimulatedState,
+    tracing: bool,
+}
+
+impl Default for SimulatedDevice {
+    fn default() -> Self {
+        SimulatedDevice::new()
+    }
+}
+
+impl SimulatedDevice {
+    pub fn new() -> Self {
+        SimulatedDevice {
+            state:   SimulatedState::new(),
+            tracing: false,
+        }
+    }
+
+    pub fn add_output(
+        mut self,
+        type_: ConnectorType,
+        status: ConnectorStatus,
+        width: u16,
+        height: u16,
+        refresh: u32,
+    ) -> SimulatedDevice {
+        let state = &mut self.state;
+
+        let crtc = state.add_object(ObjectType::Crtc, &[]);
+        let crtc_mask = 1 << state.crtcs.len();
+        state.crtcs.push(crtc);
+
+        let encoder_type = match type_ {
+            ConnectorType::VGA | ConnectorType::DVIA => ENCODER_TYPE_DAC,
+            ConnectorType::DVID |
+            ConnectorType::DVII |
+            ConnectorType::HDMIA |
+            ConnectorType::HDMIB => ENCODER_TYPE_TMDS,
+            ConnectorType::LVDS => ENCODER_TYPE_LVDS,
+            ConnectorType::DSI => ENCODER_TYPE_DSI,
+            _ => ENCODER_TYPE_VIRTUAL,
+        };
+
+        let encoder = state.add_object(ObjectType::Encoder, &[]);
+        state.encoders.push(SimulatedEncoder {
+            id:             encoder,
+            type_:          encoder_type,
+            possible_crtcs: crtc_mask,
+        });
+
+        let type_id = state
+            .connectors
+            .iter()
+            .filter(|connector| connector.type_ == u32::from(type_))
+            .count() as u32 +
+            1;
+
+        let connector = state.add_object(ObjectType::Connector, &[]);
+        state.connectors.push(SimulatedConnector {
+            id: connector,
+            type_: type_.into(),
+            type_id,
+            status: status.into(),
+            encoders: vec![encoder],
+            modes: vec![simulated_mode(width, height, refresh)],
+            mm_width: width as u32 / 4,
+            mm_height: height as u32 / 4,
+        });
+
+        self.add_plane(PLANE_TYPE_PRIMARY, crtc_mask)
+    }
+
+    pub fn add_overlay_plane(self, possible_crtcs: u32) -> SimulatedDevice {
+        self.add_plane(PLANE_TYPE_OVERLAY, possible_crtcs)
+    }
+
+    fn add_plane(mut self, type_: u64, possible_crtcs: u32) -> SimulatedDevice {
+        let defaults = [
+            ("type", type_),
+            ("rotation", 1),
+            ("alpha", 0xffff),
+            ("pixel blend mode", 1),
+        ];
+        let plane = self.state.add_object(ObjectType::Plane, &defaults);
+
+        self.state.planes.push(SimulatedPlane {
+            id: plane,
+            possible_crtcs,
+            formats: vec![FORMAT_XRGB8888, FORMAT_RGB888],
+        });
+
+        self
+    }
+
+    pub fn into_device(self) -> Result<Device> {
+        let fd = cvt(unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) })?;
+
+        let backend = SimulatedBackend {
+            state: Mutex::new(self.state),
+            fd:    unsafe { OwnedFd::from_raw_fd(fd) },
+        };
+
+        Device::default_builder()
+            .set_tracing(self.tracing)
+            .build(Box::new(backend))
+    }
+
+    pub fn set_tracing(mut self, tracing: bool) -> SimulatedDevice {
+        self.tracing = tracing;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct SimulatedBackend {
+    state: Mutex<SimulatedState>,
+    fd:    OwnedFd,
+}
+
+impl SimulatedBackend {
+    fn lock(&self) -> MutexGuard<'_, SimulatedState> {
+        match self.state.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Backend for SimulatedBackend {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+
+    fn allocate_dumb_buffer(
+        &self,
+        width: usize,
+        height: usize,
+        bpp: usize,
+    ) -> Result<drm_mode_create_dumb> {
+        let mut state = self.lock();
+
+        if width == 0 || height == 0 || bpp == 0 {
+            return Err(errno("CREATE_DUMB", libc::EINVAL));
+        }
+
+        let pitch = width * bpp.div_ceil(8);
+        let handle = state.next_handle;
+        state.next_handle += 1;
+
+        let dumb = drm_mode_create_dumb {
+            height: height.try_into()?,
+            width: width.try_into()?,
+            bpp: bpp.try_into()?,
+            flags: 0,
+            handle,
+            pitch: pitch.try_into()?,
+            size: (pitch * height).try_into()?,
+        };
+
+        state.dumbs.insert(handle, dumb);
+
+        Ok(dumb)
+    }
+
+    fn add_framebuffer(
+        &self,
+        handle: u32,
+        width: u32,
+        pitch: u32,
+        height: u32,
+        _fmt: u32,
+    ) -> Result<u32> {
+        let mut state = self.lock();
+
+        let dumb = state
+            .dumbs
+            .get(&handle)
+            .ok_or_else(|| errno("ADDFB2", libc::ENOENT))?;
+        if width > dumb.width || height > dumb.height || pitch > dumb.pitch {
+            return Err(errno("ADDFB2", libc::EINVAL));
+        }
+
+        let id = state.add_object(ObjectType::Fb, &[]);
+        state.framebuffers.insert(id, SimulatedFramebuffer {
+            width,
+            height,
+        });
+
+        Ok(id)
+    }
+
+    fn atomic_commit(
+        &self,
+        objs_ptr: Vec<u32>,
+        count_props_ptr: Vec<u32>,
+        props_ptr: Vec<u32>,
+        prop_values_ptr: Vec<u64>,
+        flags: u32,
+        user_data: u64,
+    ) -> Result<()> {
+        let mut state = self.lock();
+
+        if !state.client_caps.contains(&(ClientCapability::Atomic as u64)) {
+            return Err(errno("ATOMIC", libc::EOPNOTSUPP));
+        }
+
+        let supported = DRM_MODE_PAGE_FLIP_EVENT |
+            DRM_MODE_ATOMIC_TEST_ONLY |
+            DRM_MODE_ATOMIC_NONBLOCK |
+            DRM_MODE_ATOMIC_ALLOW_MODESET;
+
+        // There's no asynchronous flip support in the simulated driver
+        if flags & !supported != 0 {
+            return Err(rejected("ATOMIC"));
+        }
+
+        let test_only = flags & DRM_MODE_ATOMIC_TEST_ONLY != 0;
+        if test_only && flags & DRM_MODE_PAGE_FLIP_EVENT != 0 {
+            return Err(rejected("ATOMIC"));
+        }
+
+        let mut objects = state.objects.clone();
+        let mut props = props_ptr.iter().zip(prop_values_ptr.iter());
+
+        for (obj_id, count) in objs_ptr.iter().zip(count_props_ptr.iter()) {
+            let object = object_mut("ATOMIC", &mut objects, *obj_id)?;
+
+            for _ in 0..*count {
+                let (prop_id, value) =
+                    props.next().ok_or_else(|| errno("ATOMIC", libc::EFAULT))?;
+
+                let prop = state
+                    .properties
+                    .iter()
+                    .find(|prop| prop.id == *prop_id)
+                    .ok_or_else(|| errno("ATOMIC", libc::ENOENT))?;
+
+                if prop.flags & DRM_MODE_PROP_IMMUTABLE != 0 ||
+                    !prop.is_valid(&state, *value)
+                {
+                    return Err(rejected("ATOMIC"));
+                }
+
+                object.set("ATOMIC", *prop_id, *value)?;
+            }
+        }
+
+        state.check("ATOMIC", &objects)?;
+
+        if flags & DRM_MODE_ATOMIC_ALLOW_MODESET == 0 &&
+            state.needs_modeset(&objects)
+        {
+            return Err(rejected("ATOMIC"));
+        }
+
+        let crtcs = state.affected_crtcs(&objs_ptr, &objects);
+        if flags & DRM_MODE_PAGE_FLIP_EVENT != 0 {
+            let active = state.property_id(ObjectType::Crtc, "ACTIVE");
+
+            let inactive = crtcs.is_empty() ||
+                crtcs.iter().any(|crtc| {
+                    objects.get(crtc).map_or(0, |obj| obj.get(active)) == 0
+                });
+
+            if inactive {
+                return Err(rejected("ATOMIC"));
+            }
+        }
+
+        if test_only {
+            return Ok(());
+        }
+
+        state.objects = objects;
+
+        if flags & DRM_MODE_PAGE_FLIP_EVENT != 0 {
+            state.queue_flip_events(&crtcs, user_data);
+        }
+
+        Ok(())
+    }
+
+    fn auth_magic(&self, magic: u32) -> Result<()> {
+        let mut state = self.lock();
+
+        if !state.master {
+            return Err(errno("AUTH_MAGIC", libc::EACCES).into_not_master());
+        }
+
+        let idx = state
+            .magics
+            .iter()
+            .position(|m| *m == magic)
+            .ok_or_else(|| errno("AUTH_MAGIC", libc::EINVAL))?;
+
+        state.magics.remove(idx);
+        Ok(())
+    }
+
+    fn create_lease(
+        &self,
+        _objects: &[u32],
+        _flags: libc::c_int,
+    ) -> Result<(u32, OwnedFd)> {
+        Err(errno("CREATE_LEASE", libc::EOPNOTSUPP))
+    }
+
+    fn create_property_blob(&self, data: &[u8]) -> Result<u32> {
+        let mut state = self.lock();
+
+        if data.is_empty() {
+            return Err(errno("CREATEPROPBLOB", libc::EINVAL));
+        }
+
+        let id = state.add_object(ObjectType::Blob, &[]);
+        state.blobs.insert(id, data.to_vec());
+
+        Ok(id)
+    }
+
+    fn destroy_property_blob(&self, id: u32) -> Result<()> {
+        let mut state = self.lock();
+
+        if !state.blobs.contains_key(&id) {
+            return Err(errno("DESTROYPROPBLOB", libc::EINVAL));
+        }
+
+        // Blobs still in use stay around, like the kernel refcounting does
+        let in_use = state.objects.values().any(|obj| {
+            obj.properties.iter().any(|(prop_id, value)| {
+                *value == id as u64 &&
+                    state.properties.iter().any(|prop| {
+                        prop.id == *prop_id &&
+                            prop.flags & DRM_MODE_PROP_BLOB != 0
+                    })
+            })
+        });
+
+        if !in_use {
+            state.blobs.remove(&id);
+            state.objects.remove(&id);
+        }
+
+        Ok(())
+    }
+
+    fn remove_framebuffer(&self, id: u32) {
+        let mut state = self.lock();
+
+        if state.framebuffers.remove(&id).is_none() {
+            return;
+        }
+
+        state.objects.remove(&id);
+
+        // Like the kernel, disable any plane still scanning out from it
+        let fb_prop = state.property_id(ObjectType::Plane, "FB_ID");
+        let crtc_prop = state.property_id(ObjectType::Plane, "CRTC_ID");
+        for object in state.objects.values_mut() {
+            if object.type_ == ObjectType::Plane as u32 &&
+                object.get(fb_prop) == id as u64
+            {
+                let _ = object.set("RMFB", fb_prop, 0);
+                let _ = object.set("RMFB", crtc_prop, 0);
+            }
+        }
+    }
+
+    fn drop_master(&self) -> Result<()> {
+        let mut state = self.lock();
+
+        if !state.master {
+            return Err(errno("DROP_MASTER", libc::EINVAL));
+        }
+
+        state.master = false;
+        Ok(())
+    }
+
+    fn destroy_dumb_buffer(&self, handle: u32) {
+        self.lock().dumbs.remove(&handle);
+    }
+
+    fn get_capability(&self, cap: u64) -> Result<u64> {
+        let value = match cap {
+            // DUMB_BUFFER
+            0x01 => 1,
+            // DUMB_PREFERRED_DEPTH
+            0x03 => 24,
+            // TIMESTAMP_MONOTONIC
+            0x06 => 1,
+            // CURSOR_WIDTH and CURSOR_HEIGHT
+            0x08 | 0x09 => 64,
+            // CRTC_IN_VBLANK_EVENT
+            0x12 => 1,
+            0x04 | 0x05 | 0x07 | 0x10 | 0x11 | 0x13 | 0x15 => 0,
+            _ => return Err(errno("GET_CAP", libc::EINVAL)),
+        };
+
+        Ok(value)
+    }
+
+    fn get_driver_version(
+        &self,
+        name: &mut Vec<u8>,
+        date: &mut Vec<u8>,
+        desc: &mut Vec<u8>,
+    ) -> Result<drm_version> {
+        *name = DRIVER_NAME.as_bytes().to_vec();
+        *date = DRIVER_DATE.as_bytes().to_vec();
+        *desc = DRIVER_DESC.as_bytes().to_vec();
+
+        Ok(drm_version {
+            version_major: 1,
+            version_minor: 0,
+            version_patchlevel: 0,
+            name_len: name.len(),
+            name: name.as_mut_ptr() as usize,
+            date_len: date.len(),
+            date: date.as_mut_ptr() as usize,
+            desc_len: desc.len(),
+            desc: desc.as_mut_ptr() as usize,
+        })
+    }
+
+    fn get_encoder(&self, id: u32) -> Result<drm_mode_get_encoder> {
+        let state = self.lock();
+
+        let encoder = state
+            .encoders
+            .iter()
+            .find(|encoder| encoder.id == id)
+            .ok_or_else(|| errno("GETENCODER", libc::ENOENT))?;
+
+        Ok(drm_mode_get_encoder {
+            encoder_id: encoder.id,
+            encoder_type: encoder.type_,
+            crtc_id: 0,
+            possible_crtcs: encoder.possible_crtcs,
+            possible_clones: 0,
+        })
+    }
+
+    fn get_connector(
+        &self,
+        id: u32,
+        modes: Option<&mut Vec<drm_mode_modeinfo>>,
+        encoders: Option<&mut Vec<u32>>,
+        properties: Option<&mut Vec<u32>>,
+    ) -> Result<drm_mode_get_connector> {
+        if properties.is_some() {
+            return Err(Error::UnsupportedError);
+        }
+
+        let state = self.lock();
+
+        let connector = state
+            .connectors
+            .iter()
+            .find(|connector| connector.id == id)
+            .ok_or_else(|| errno("GETCONNECTOR", libc::ENOENT))?;
+        let object =
+            state.get_object("GETCONNECTOR", id, ObjectType::Connector)?;
+
+        if let Some(modes) = modes {
+            *modes = connector.modes.clone();
+        }
+
+        if let Some(encoders) = encoders {
+            *encoders = connector.encoders.clone();
+        }
+
+        Ok(drm_mode_get_connector {
+            count_modes: connector.modes.len().try_into()?,
+            count_props: object.properties.len().try_into()?,
+            count_encoders: connector.encoders.len().try_into()?,
+            connector_id: connector.id,
+            connector_type: connector.type_,
+            connector_type_id: connector.type_id,
+            connection: connector.status,
+            mm_width: connector.mm_width,
+            mm_height: connector.mm_height,
+            ..Default::default()
+        })
+    }
+
+    fn get_connectors(&self) -> Result<Vec<drm_mode_get_connector>> {
+        let ids: Vec<u32> = self
+            .lock()
+            .connectors
+            .iter()
+            .map(|connector| connector.id)
+            .collect();
+
+        let mut connectors = Vec::with_capacity(ids.len());
+        for id in ids {
+            connectors.push(self.get_connector(id, None, None, None)?);
+        }
+
+        Ok(connectors)
+    }
+
+    fn get_crtcs(&self) -> Result<Vec<drm_mode_crtc>> {
+        let state = self.lock();
+        let mode_prop = state.property_id(ObjectType::Crtc, "MODE_ID");
+
+        let mut crtcs = Vec::with_capacity(state.crtcs.len());
+        for id in &state.crtcs {
+            let object = state.get_object("GETCRTC", *id, ObjectType::Crtc)?;
+            let mut crtc = drm_mode_crtc {
+                crtc_id: *id,
+                ..Default::default()
+            };
+
+            let mode = object.get(mode_prop) as u32;
+            if let Some(blob) = state.blobs.get(&mode) {
+                let size = std::mem::size_of::<drm_mode_modeinfo>();
+                let ptr = &mut crtc.mode as *mut drm_mode_modeinfo as *mut u8;
+
+                unsafe {
+                    std::ptr::copy_nonoverlapping(blob.as_ptr(), ptr, size)
+                };
+                crtc.mode_valid = 1;
+            }
+
+            crtcs.push(crtc);
+        }
+
+        Ok(crtcs)
+    }
+
+    fn get_plane(&self, id: u32) -> Result<drm_mode_get_plane> {
+        let state = self.lock();
+
+        let plane = state
+            .planes
+            .iter()
+            .find(|plane| plane.id == id)
+            .ok_or_else(|| errno("GETPLANE", libc::ENOENT))?;
+        let object = state.get_object("GETPLANE", id, ObjectType::Plane)?;
+
+        let fb_prop = state.property_id(ObjectType::Plane, "FB_ID");
+        let crtc_prop = state.property_id(ObjectType::Plane, "CRTC_ID");
+
+        Ok(drm_mode_get_plane {
+            plane_id: plane.id,
+            crtc_id: object.get(crtc_prop) as u32,
+            fb_id: object.get(fb_prop) as u32,
+            possible_crtcs: plane.possible_crtcs,
+            gamma_size: 0,
+            count_format_types: plane.formats.len().try_into()?,
+            format_type_ptr: 0,
+        })
+    }
+
+    fn get_planes(&self) -> Result<Vec<drm_mode_get_plane>> {
+        let ids: Vec<u32> =
+            self.lock().planes.iter().map(|plane| plane.id).collect();
+
+        let mut planes = Vec::with_capacity(ids.len());
+        for id in ids {
+            planes.push(self.get_plane(id)?);
+        }
+
+        Ok(planes)
+    }
+
+    fn get_lease(&self) -> Result<Vec<u32>> {
+        Ok(Vec::new())
+    }
+
+    fn get_magic(&self) -> Result<u32> {
+        let mut state = self.lock();
+
+        let magic = state.next_magic;
+        state.next_magic += 1;
+        state.magics.push(magic);
+
+        Ok(magic)
+    }
+
+    fn get_property(
+        &self,
+        id: u32,
+        values: Option<&mut Vec<u64>>,
+        enums: Option<&mut Vec<drm_mode_property_enum>>,
+    ) -> Result<drm_mode_get_property> {
+        let state = self.lock();
+
+        let prop = state
+            .properties
+            .iter()
+            .find(|prop| prop.id == id)
+            .ok_or_else(|| errno("GETPROPERTY", libc::ENOENT))?;
+
+        // Internally, the names are prefixed with the object type
+        let name = prop.name.split_once(':').map_or(&*prop.name, |x| x.1);
+
+        // Like the kernel, the values of an enum are reported twice
+        let prop_values: Vec<u64> =
+            if prop.flags & (DRM_MODE_PROP_ENUM | DRM_MODE_PROP_BITMASK) != 0 {
+                prop.enums.iter().map(|(value, _)| *value).collect()
+            } else {
+                prop.values.clone()
+            };
+
+        let count_enum_blobs = if prop.flags & DRM_MODE_PROP_BLOB != 0 {
+            0
+        } else {
+            prop.enums.len().try_into()?
+        };
+
+        if let Some(values) = values {
+            *values = prop_values.clone();
+        }
+
+        if let Some(enums) = enums {
+            *enums = prop
+                .enums
+                .iter()
+                .map(|(value, name)| drm_mode_property_enum {
+                    value: *value,
+                    name:  fill_name(name),
+                })
+                .collect();
+        }
+
+        Ok(drm_mode_get_property {
+            prop_id: prop.id,
+            flags: prop.flags,
+            name: fill_name(name),
+            count_values: prop_values.len().try_into()?,
+            count_enum_blobs,
+            ..Default::default()
+        })
+    }
+
+    fn get_properties(
+        &self,
+        object_type: u32,
+        object_id: u32,
+    ) -> Result<Vec<(u32, u64)>> {
+        let state = self.lock();
+
+        let object = state
+            .objects
+            .get(&object_id)
+            .filter(|obj| {
+                object_type == ObjectType::Any as u32 ||
+                    obj.type_ == object_type
+            })
+            .ok_or_else(|| errno("OBJ_GETPROPERTIES", libc::ENOENT))?;
+
+        Ok(object.properties.clone())
+    }
+
+    fn get_property_blob(&self, id: u32) -> Result<Vec<u8>> {
+        let state = self.lock();
+
+        state
+            .blobs
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| errno("GETPROPBLOB", libc::ENOENT))
+    }
+
+    fn is_master(&self) -> Result<bool> {
+        Ok(self.lock().master)
+    }
+
+    fn list_lessees(&self) -> Result<Vec<u32>> {
+        Ok(Vec::new())
+    }
+
+    fn map_dumb_buffer(&self, handle: u32, size: usize) -> Result<MemoryMap> {
+        let state = self.lock();
+
+        let dumb = state
+            .dumbs
+            .get(&handle)
+            .ok_or_else(|| errno("MAP_DUMB", libc::ENOENT))?;
+        if size as u64 > dumb.size {
+            return Err(errno("MAP_DUMB", libc::EINVAL));
+        }
+
+        let mapping = MemoryMap::new(size, &[
+            MapOption::MapReadable,
+            MapOption::MapWritable,
+        ])?;
+
+        Ok(mapping)
+    }
+
+    fn read_events(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.lock();
+        let mut len = 0;
+
+        // Like the kernel, only ever hand out whole events
+        while let Some(event) = state.events.front() {
+            if len + event.len() > buf.len() {
+                break;
+            }
+
+            buf[len..len + event.len()].copy_from_slice(event);
+            len += event.len();
+            state.events.pop_front();
+        }
+
+        Ok(len)
+    }
+
+    fn revoke_lease(&self, _lessee_id: u32) -> Result<()> {
+        Err(errno("REVOKE_LEASE", libc::ENOENT))
+    }
+
+    fn set_crtc(
+        &self,
+        fb_id: u32,
+        crtc_id: u32,
+        connectors: &[u32],
+        x: u32,
+        y: u32,
+        mode: Option<&drm_mode_modeinfo>,
+    ) -> Result<()> {
+        let mut state = self.lock();
+
+        if !state.master {
+            return Err(errno("SETCRTC", libc::EACCES).into_not_master());
+        }
+
+        state.get_object("SETCRTC", crtc_id, ObjectType::Crtc)?;
+        let index = state
+            .crtc_index(crtc_id)
+            .ok_or_else(|| errno("SETCRTC", libc::ENOENT))?;
+
+        let (src_w, src_h) = match state.framebuffers.get(&fb_id) {
+            Some(fb) => (fb.width, fb.height),
+            None if fb_id == 0 => (0, 0),
+            None => return Err(errno("SETCRTC", libc::ENOENT)),
+        };
+
+        // Like the kernel, the viewport has to fit in the framebuffer
+        if x > src_w || y > src_h {
+            return Err(errno("SETCRTC", libc::ENOSPC));
+        }
+
+        let mode_id = match mode {
+            Some(mode) => {
+                let id = state.add_object(ObjectType::Blob, &[]);
+                state.blobs.insert(id, mode.as_bytes().to_vec());
+                id
+            },
+            None => 0,
+        };
+
+        let type_prop = state.property_id(ObjectType::Plane, "type");
+        let primary = state
+            .planes
+            .iter()
+            .find(|plane| {
+                let type_ = state
+                    .objects
+                    .get(&plane.id)
+                    .map_or(0, |obj| obj.get(type_prop));
+
+                type_ == PLANE_TYPE_PRIMARY &&
+                    plane.possible_crtcs & (1 << index) != 0
+            })
+            .map(|plane| plane.id)
+            .ok_or_else(|| rejected("SETCRTC"))?;
+
+        let enabled = mode_id != 0;
+        let crtc_value = if enabled { crtc_id as u64 } else { 0 };
+
+        let plane_props = [
+            ("FB_ID", if enabled { fb_id as u64 } else { 0 }),
+            ("CRTC_ID", crtc_value),
+            ("SRC_X", (x as u64) << 16),
+            ("SRC_Y", (y as u64) << 16),
+            ("SRC_W", ((src_w - x) as u64) << 16),
+            ("SRC_H", ((src_h - y) as u64) << 16),
+            ("CRTC_X", 0),
+            ("CRTC_Y", 0),
+            ("CRTC_W", mode.map_or(0, |m| m.hdisplay as u64)),
+            ("CRTC_H", mode.map_or(0, |m| m.vdisplay as u64)),
+        ];
+
+        let mut objects = state.objects.clone();
+
+        for (name, value) in plane_props.iter() {
+            let prop = state.property_id(ObjectType::Plane, name);
+
+            object_mut("SETCRTC", &mut objects, primary)?
+                .set("SETCRTC", prop, *value)?;
+        }
+
+        let active = state.property_id(ObjectType::Crtc, "ACTIVE");
+        let mode_prop = state.property_id(ObjectType::Crtc, "MODE_ID");
+        let crtc = object_mut("SETCRTC", &mut objects, crtc_id)?;
+        crtc.set("SETCRTC", active, enabled as u64)?;
+        crtc.set("SETCRTC", mode_prop, mode_id as u64)?;
+
+        let conn_prop = state.property_id(ObjectType::Connector, "CRTC_ID");
+        for connector in &state.connectors {
+            let object = object_mut("SETCRTC", &mut objects, connector.id)?;
+
+            if connectors.contains(&connector.id) {
+                object.set("SETCRTC", conn_prop, crtc_value)?;
+            } else if object.get(conn_prop) == crtc_id as u64 {
+                object.set("SETCRTC", conn_prop, 0)?;
+            }
+        }
+
+        state.check("SETCRTC", &objects)?;
+        state.objects = objects;
+
+        Ok(())
+    }
+
+    fn set_client_capability(&self, cap: u64) -> Result<()> {
+        let mut state = self.lock();
+
+        // STEREO_3D, UNIVERSAL_PLANES, ATOMIC, ASPECT_RATIO and
+        // WRITEBACK_CONNECTORS
+        if !(1..=5).contains(&cap) {
+            return Err(errno("SET_CLIENT_CAP", libc::EINVAL));
+        }
+
+        if !state.client_caps.contains(&cap) {
+            state.client_caps.push(cap);
+        }
+
+        Ok(())
+    }
+
+    fn set_master(&self) -> Result<()> {
+        self.lock().master = true;
+
+        Ok(())
+    }
+
+    fn set_plane(
+        &self,
+        fb_id: u32,
+        plane_id: u32,
+        crtc_id: u32,
+        w: u32,
+        h: u32,
+    ) -> Result<()> {
+        let mut state = self.lock();
+
+        if !state.master {
+            return Err(errno("SETPLANE", libc::EACCES).into_not_master());
+        }
+
+        let props = [
+            ("FB_ID", fb_id as u64),
+            ("CRTC_ID", crtc_id as u64),
+            ("SRC_X", 0),
+            ("SRC_Y", 0),
+            ("SRC_W", (w as u64) << 16),
+            ("SRC_H", (h as u64) << 16),
+            ("CRTC_W", w as u64),
+            ("CRTC_H", h as u64),
+        ];
+
+        let mut objects = state.objects.clone();
+        for (name, value) in props.iter() {
+            let prop = state.property_id(ObjectType::Plane, name);
+
+            objects
+                .get_mut(&plane_id)
+                .filter(|obj| obj.type_ == ObjectType::Plane as u32)
+                .ok_or_else(|| errno("SETPLANE", libc::ENOENT))?
+                .set("SETPLANE", prop, *value)?;
+        }
+
+        state.check("SETPLANE", &objects)?;
+        state.objects = objects;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use std::sync::Arc;
+
+    use crate::Buffer;
+    use crate::BufferType;
+    use crate::ConnectorStatus;
+    use crate::ConnectorType;
+    use crate::Device;
+    use crate::Format;
+    use crate::PipelineInit;
+
+    use super::SimulatedDevice;
+
+    // A single connected 1280x720 HDMI output
+    pub(crate) fn hdmi_output() -> SimulatedDevice {
+        SimulatedDevice::new().add_output(
+            ConnectorType::HDMIA,
+            ConnectorStatus::Connected,
+            1280,
+            720,
+            60,
+        )
+    }
+
+    pub(crate) fn hdmi_device() -> Device {
+        hdmi_output().into_device().unwrap()
+    }
+
+    pub(crate) fn framebuffer(dev: &Device) -> Arc<Buffer> {
+        let buffer = dev
+            .allocate_buffer(BufferType::Dumb, 1280, 720, 32)
+            .unwrap()
+            .into_framebuffer(Format::XRGB8888)
+            .unwrap();
+
+        Arc::new(buffer)
+    }
+
+    // Routes the first connector, in its first mode, to display buffer
+    pub(crate) fn pipeline_init(
+        dev: &Device,
+        buffer: &Arc<Buffer>,
+    ) -> PipelineInit {
+        let connector = dev.get_connectors().unwrap().remove(0);
+        let mode = connector.get_modes().unwrap().remove(0);
+
+        PipelineInit::new(dev)
+            .discover(connector)
+            .unwrap()
+            .set_mode(mode)
+            .set_buffer(buffer.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::AtomicRequest;
+    use crate::BufferType;
+    use crate::CommitOptions;
+    use crate::ConnectorStatus;
+    use crate::ConnectorType;
+    use crate::Error;
+    use crate::Event;
+    use crate::Format;
+    use crate::ModeType;
+    use crate::Object;
+    use crate::PipelineInit;
+
+    use super::fixtures;
+    use super::SimulatedDevice;
+
+    #[test]
+    fn test_simulated_pipeline() {
+        let dev = SimulatedDevice::new()
+            .add_output(
+                ConnectorType::HDMIA,
+                ConnectorStatus::Connected,
+                1920,
+                1080,
+                60,
+            )
+            .add_overlay_plane(1)
+            .into_device()
+            .unwrap();
+
+        let connector = dev
+            .get_connectors()
+            .unwrap()
+            .into_iter()
+            .find(|con| con.get_status() == ConnectorStatus::Connected)
+            .unwrap();
+        assert_eq!(connector.get_type(), ConnectorType::HDMIA);
+
+        let mode = connector
+            .get_modes()
+            .unwrap()
+            .into_iter()
+            .find(|mode| mode.has_type(ModeType::Preferred))
+            .unwrap();
+        assert_eq!(mode.width(), 1920);
+        assert_eq!(mode.height(), 1080);
+
+        let first = dev
+            .allocate_buffer(BufferType::Dumb, 640, 480, 32)
+            .unwrap()
+            .map()
+            .unwrap()
+            .into_framebuffer(Format::XRGB8888)
+            .unwrap();
+
+        let second = dev
+            .allocate_buffer(BufferType::Dumb, 320, 240, 32)
+            .unwrap()
+            .into_framebuffer(Format::XRGB8888)
+            .unwrap();
+
+        let pipeline = PipelineInit::new(&dev)
+            .discover(connector)
+            .unwrap()
+            .set_mode(mode)
+            .set_buffer(Arc::new(first))
+            .set_plane_coordinates(10, 10)
+            .build()
+            .unwrap();
+
+        let pipeline = pipeline
+            .update_buffer(Arc::new(second))
+            .unwrap()
+            .update_plane_size(320, 240)
+            .unwrap()
+            .commit()
+            .unwrap();
+
+        // The source rectangle is now larger than the framebuffer
+        assert!(pipeline
+            .update_plane_size(640, 480)
+            .unwrap()
+            .commit()
+            .is_err());
+    }
+
+    #[test]
+    fn test_simulated_disconnected_output() {
+        let dev = SimulatedDevice::new()
+            .add_output(
+                ConnectorType::DisplayPort,
+                ConnectorStatus::Disconnected,
+                1280,
+                720,
+                60,
+            )
+            .into_device()
+            .unwrap();
+
+        let connectors = dev.get_connectors().unwrap();
+        let connector = connectors.into_iter().next().unwrap();
+        let driver = dev.get_driver_info().unwrap();
+
+        assert_eq!(connector.get_status(), ConnectorStatus::Disconnected);
+        assert_eq!(driver.get_name(), "doremi-sim");
+    }
+
+    #[test]
+    fn test_simulated_set_crtc() {
+        let dev = fixtures::hdmi_device();
+        let buffer = fixtures::framebuffer(&dev);
+        let connector = dev.get_connectors().unwrap().remove(0);
+        let mode = connector.get_modes().unwrap().remove(0);
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+
+        dev.set_crtc(&buffer, &crtc, &[&connector], 0, 0, Some(&mode))
+            .unwrap();
+
+        // The viewport starts past the end of the framebuffer
+        let err = dev
+            .set_crtc(&buffer, &crtc, &[&connector], 1281, 0, Some(&mode))
+            .unwrap_err();
+        assert!(matches!(err, Error::NoSpaceError(_)));
+    }
+
+    #[test]
+    fn test_simulated_flip_events() {
+        let dev = fixtures::hdmi_device();
+        let buffer = fixtures::framebuffer(&dev);
+
+        let pipeline = fixtures::pipeline_init(&dev, &buffer).build().unwrap();
+        assert!(dev.read_events().unwrap().is_empty());
+
+        let options = CommitOptions::new()
+            .set_nonblocking(true)
+            .set_page_flip_event(true)
+            .set_user_data(0xcafe);
+        let _pipeline = pipeline.commit_with(options).unwrap();
+
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+        match dev.read_events().unwrap()[..] {
+            [Event::FlipComplete(event)] => {
+                assert_eq!(event.get_crtc_id(), crtc.get_id());
+                assert_eq!(event.get_user_data(), 0xcafe);
+            },
+            _ => panic!("Expected a single flip complete event"),
+        }
+
+        // Shutting the output down is a modeset
+        let connector = dev.get_connectors().unwrap().remove(0);
+        let plane = crtc.get_possible_planes().unwrap().remove(0);
+        let request = AtomicRequest::new(&dev)
+            .add_property(&plane, "FB_ID", 0)
+            .unwrap()
+            .add_property(&plane, "CRTC_ID", 0)
+            .unwrap()
+            .add_property(&crtc, "ACTIVE", 0)
+            .unwrap()
+            .add_property(&crtc, "MODE_ID", 0)
+            .unwrap()
+            .add_property(&connector, "CRTC_ID", 0)
+            .unwrap();
+
+        assert!(request.commit_with(CommitOptions::new()).is_err());
+        assert!(request
+            .commit_with(CommitOptions::new().set_page_flip_event(true))
+            .is_err());
+        request.commit().unwrap();
+    }
+}
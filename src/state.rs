@@ -0,0 +1,88 @@
+use crate::atomic::AtomicRequest;
+use crate::device::Device;
+use crate::error::Result;
+
+// Puts the display back the way it was found, so that whoever had it
+// before us (fbcon, a compositor) doesn't come back to our leftovers.
+#[derive(Debug)]
+pub struct StateGuard {
+    snapshot: AtomicRequest,
+}
+
+impl StateGuard {
+    pub fn new(dev: &Device) -> Result<StateGuard> {
+        Ok(StateGuard {
+            snapshot: dev.snapshot_state()?,
+        })
+    }
+
+    pub fn get_snapshot(&self) -> &AtomicRequest {
+        &self.snapshot
+    }
+
+    pub fn restore(&self) -> Result<()> {
+        self.snapshot.commit()
+    }
+}
+
+impl Drop for StateGuard {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulated::fixtures;
+    use crate::Device;
+    use crate::Object;
+
+    use super::StateGuard;
+
+    #[test]
+    fn test_state_guard() {
+        let dev = fixtures::hdmi_device();
+        let buffer = fixtures::framebuffer(&dev);
+
+        let values = |dev: &Device| -> Vec<_> {
+            dev.snapshot_state()
+                .unwrap()
+                .iter()
+                .map(|prop| prop.get_value())
+                .collect()
+        };
+
+        let initial = values(&dev);
+
+        let guard = StateGuard::new(&dev).unwrap();
+        assert_eq!(guard.get_snapshot().len(), initial.len());
+
+        let _pipeline = fixtures::pipeline_init(&dev, &buffer).build().unwrap();
+        assert_ne!(values(&dev), initial);
+
+        drop(guard);
+        assert_eq!(values(&dev), initial);
+    }
+
+    #[test]
+    fn test_state_mode_copy() {
+        let dev = fixtures::hdmi_device();
+        let buffer = fixtures::framebuffer(&dev);
+        let _pipeline = fixtures::pipeline_init(&dev, &buffer).build().unwrap();
+
+        let crtc = dev.get_crtcs().unwrap().remove(0);
+        let mode_id = crtc.get_property_id("MODE_ID").unwrap();
+
+        let snapshot = dev.snapshot_state().unwrap();
+        let copy = snapshot
+            .iter()
+            .find(|prop| prop.get_property_id() == mode_id)
+            .unwrap()
+            .get_value() as u32;
+        assert!(dev.get_blob(copy).is_ok());
+
+        // The copy of the mode goes away with the snapshot
+        drop(snapshot);
+        assert!(dev.get_blob(copy).is_err());
+    }
+}
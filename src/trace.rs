@@ -0,0 +1,1382 @@
+use std::convert::TryFrom;
+use std::os::unix::io::AsFd;
+use std::os::unix::io::BorrowedFd;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::io::OwnedFd;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use cvt::cvt;
+use mmap::MapOption;
+use mmap::MemoryMap;
+
+use crate::backend::Backend;
+use crate::device::ClientCapability;
+use crate::device::Device;
+use crate::device::DeviceBuilder;
+use crate::error::Error;
+use crate::error::Result;
+use crate::rawdevice::drm_mode_create_dumb;
+use crate::rawdevice::drm_mode_crtc;
+use crate::rawdevice::drm_mode_get_connector;
+use crate::rawdevice::drm_mode_get_encoder;
+use crate::rawdevice::drm_mode_get_plane;
+use crate::rawdevice::drm_mode_get_property;
+use crate::rawdevice::drm_mode_modeinfo;
+use crate::rawdevice::drm_mode_property_enum;
+use crate::rawdevice::drm_version;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+enum TraceCall {
+    AddFramebuffer {
+        handle: u32,
+        width:  u32,
+        pitch:  u32,
+        height: u32,
+        format: u32,
+    },
+    AtomicCommit {
+        objects:     Vec<u32>,
+        count_props: Vec<u32>,
+        props:       Vec<u32>,
+        values:      Vec<u64>,
+        flags:       u32,
+        user_data:   u64,
+    },
+    AuthMagic {
+        magic: u32,
+    },
+    CreateDumbBuffer {
+        width:  usize,
+        height: usize,
+        bpp:    usize,
+    },
+    CreateLease {
+        objects: Vec<u32>,
+        flags:   libc::c_int,
+    },
+    CreatePropertyBlob {
+        data: Vec<u8>,
+    },
+    DestroyDumbBuffer {
+        handle: u32,
+    },
+    DestroyPropertyBlob {
+        id: u32,
+    },
+    DropMaster,
+    GetCapability {
+        capability: u64,
+    },
+    GetConnector {
+        id:         u32,
+        modes:      bool,
+        encoders:   bool,
+        properties: bool,
+    },
+    GetConnectors,
+    GetCrtcs,
+    GetDriverVersion,
+    GetEncoder {
+        id: u32,
+    },
+    GetLease,
+    GetMagic,
+    GetPlane {
+        id: u32,
+    },
+    GetPlanes,
+    GetProperties {
+        object_type: u32,
+        object_id:   u32,
+    },
+    GetProperty {
+        id:     u32,
+        values: bool,
+        enums:  bool,
+    },
+    GetPropertyBlob {
+        id: u32,
+    },
+    IsMaster,
+    ListLessees,
+    MapDumbBuffer {
+        handle: u32,
+        size:   usize,
+    },
+    ReadEvents {
+        len: usize,
+    },
+    RemoveFramebuffer {
+        id: u32,
+    },
+    RevokeLease {
+        lessee_id: u32,
+    },
+    SetClientCapability {
+        capability: u64,
+    },
+    SetCrtc {
+        fb_id:      u32,
+        crtc_id:    u32,
+        connectors: Vec<u32>,
+        x:          u32,
+        y:          u32,
+        mode:       Option<drm_mode_modeinfo>,
+    },
+    SetMaster,
+    SetPlane {
+        fb_id:    u32,
+        plane_id: u32,
+        crtc_id:  u32,
+        w:        u32,
+        h:        u32,
+    },
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+enum TraceReply {
+    Unit,
+    Bool(bool),
+    Bytes(Vec<u8>),
+    Id(u32),
+    Ids(Vec<u32>),
+    Value(u64),
+    Connector {
+        connector:  drm_mode_get_connector,
+        modes:      Vec<drm_mode_modeinfo>,
+        encoders:   Vec<u32>,
+        properties: Vec<u32>,
+    },
+    Connectors(Vec<drm_mode_get_connector>),
+    Crtcs(Vec<drm_mode_crtc>),
+    DumbBuffer(drm_mode_create_dumb),
+    Encoder(drm_mode_get_encoder),
+    Plane(drm_mode_get_plane),
+    Planes(Vec<drm_mode_get_plane>),
+    Properties(Vec<(u32, u64)>),
+    Property {
+        property: drm_mode_get_property,
+        values:   Vec<u64>,
+        enums:    Vec<drm_mode_property_enum>,
+    },
+    Version {
+        version: drm_version,
+        name:    Vec<u8>,
+        date:    Vec<u8>,
+        desc:    Vec<u8>,
+    },
+}
+
+impl TraceCall {
+    fn get_ioctl(&self) -> &'static str {
+        match self {
+            TraceCall::AddFramebuffer {
+                ..
+            } => "ADDFB2",
+            TraceCall::AtomicCommit {
+                ..
+            } => "ATOMIC",
+            TraceCall::AuthMagic {
+                ..
+            } => "AUTH_MAGIC",
+            TraceCall::CreateDumbBuffer {
+                ..
+            } => "CREATE_DUMB",
+            TraceCall::CreateLease {
+                ..
+            } => "CREATE_LEASE",
+            TraceCall::CreatePropertyBlob {
+                ..
+            } => "CREATEPROPBLOB",
+            TraceCall::DestroyDumbBuffer {
+                ..
+            } => "DESTROY_DUMB",
+            TraceCall::DestroyPropertyBlob {
+                ..
+            } => "DESTROYPROPBLOB",
+            TraceCall::DropMaster => "DROP_MASTER",
+            TraceCall::GetCapability {
+                ..
+            } => "GET_CAP",
+            TraceCall::GetConnector {
+                ..
+            } => "GETCONNECTOR",
+            TraceCall::GetConnectors => "GETRESOURCES",
+            TraceCall::GetCrtcs => "GETRESOURCES",
+            TraceCall::GetDriverVersion => "VERSION",
+            TraceCall::GetEncoder {
+                ..
+            } => "GETENCODER",
+            TraceCall::GetLease => "GET_LEASE",
+            TraceCall::GetMagic => "GET_MAGIC",
+            TraceCall::GetPlane {
+                ..
+            } => "GETPLANE",
+            TraceCall::GetPlanes => "GETPLANERESOURCES",
+            TraceCall::GetProperties {
+                ..
+            } => "OBJ_GETPROPERTIES",
+            TraceCall::GetProperty {
+                ..
+            } => "GETPROPERTY",
+            TraceCall::GetPropertyBlob {
+                ..
+            } => "GETPROPBLOB",
+            TraceCall::IsMaster => "AUTH_MAGIC",
+            TraceCall::ListLessees => "LIST_LESSEES",
+            TraceCall::MapDumbBuffer {
+                ..
+            } => "MAP_DUMB",
+            TraceCall::ReadEvents {
+                ..
+            } => "READ",
+            TraceCall::RemoveFramebuffer {
+                ..
+            } => "RMFB",
+            TraceCall::RevokeLease {
+                ..
+            } => "REVOKE_LEASE",
+            TraceCall::SetClientCapability {
+                ..
+            } => "SET_CLIENT_CAP",
+            TraceCall::SetCrtc {
+                ..
+            } => "SETCRTC",
+            TraceCall::SetMaster => "SET_MASTER",
+            TraceCall::SetPlane {
+                ..
+            } => "SETPLANE",
+        }
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TraceEntry {
+    call:   TraceCall,
+    result: std::result::Result<TraceReply, Error>,
+}
+
+impl TraceEntry {
+    pub fn get_ioctl(&self) -> &'static str {
+        self.call.get_ioctl()
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.result.is_err()
+    }
+}
+
+// Each entry is one request made to the driver, with its arguments and
+// what came back. Requests that walk the resources (GETRESOURCES followed
+// by a GETCRTC or GETCONNECTOR per object) are recorded as one entry.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Trace {
+    entries: Vec<TraceEntry>,
+}
+
+impl Trace {
+    pub fn into_device(self) -> Result<Device> {
+        let mut builder = DeviceBuilder::new();
+
+        // Ask for the same client capabilities the traced device had, so
+        // that the replay walks through the same code paths.
+        for entry in &self.entries {
+            if let TraceCall::SetClientCapability {
+                capability,
+            } = entry.call
+            {
+                if let Ok(cap) = ClientCapability::try_from(capability) {
+                    builder = builder.request_client_capability(cap);
+                }
+            }
+        }
+
+        builder.build(Box::new(ReplayBackend::new(self)?))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TracingBackend {
+    inner:   Box<dyn Backend>,
+    entries: Mutex<Vec<TraceEntry>>,
+}
+
+impl TracingBackend {
+    pub(crate) fn new(inner: Box<dyn Backend>) -> TracingBackend {
+        TracingBackend {
+            inner,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn record<T>(
+        &self,
+        call: TraceCall,
+        result: Result<T>,
+        reply: impl FnOnce(&T) -> TraceReply,
+    ) -> Result<T> {
+        let entry = TraceEntry {
+            call,
+            result: match &result {
+                Ok(val) => Ok(reply(val)),
+                Err(err) => Err(err.clone()),
+            },
+        };
+
+        lock(&self.entries).push(entry);
+        result
+    }
+}
+
+impl Backend for TracingBackend {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+
+    fn allocate_dumb_buffer(
+        &self,
+        width: usize,
+        height: usize,
+        bpp: usize,
+    ) -> Result<drm_mode_create_dumb> {
+        let call = TraceCall::CreateDumbBuffer {
+            width,
+            height,
+            bpp,
+        };
+
+        let ret = self.inner.allocate_dumb_buffer(width, height, bpp);
+        self.record(call, ret, |dumb| TraceReply::DumbBuffer(*dumb))
+    }
+
+    fn add_framebuffer(
+        &self,
+        handle: u32,
+        width: u32,
+        pitch: u32,
+        height: u32,
+        fmt: u32,
+    ) -> Result<u32> {
+        let call = TraceCall::AddFramebuffer {
+            handle,
+            width,
+            pitch,
+            height,
+            format: fmt,
+        };
+
+        let ret = self.inner.add_framebuffer(handle, width, pitch, height, fmt);
+        self.record(call, ret, |id| TraceReply::Id(*id))
+    }
+
+    fn atomic_commit(
+        &self,
+        objs_ptr: Vec<u32>,
+        count_props_ptr: Vec<u32>,
+        props_ptr: Vec<u32>,
+        prop_values_ptr: Vec<u64>,
+        flags: u32,
+        user_data: u64,
+    ) -> Result<()> {
+        let call = TraceCall::AtomicCommit {
+            objects:     objs_ptr.clone(),
+            count_props: count_props_ptr.clone(),
+            props:       props_ptr.clone(),
+            values:      prop_values_ptr.clone(),
+            flags,
+            user_data,
+        };
+
+        let ret = self.inner.atomic_commit(
+            objs_ptr,
+            count_props_ptr,
+            props_ptr,
+            prop_values_ptr,
+            flags,
+            user_data,
+        );
+        self.record(call, ret, |_| TraceReply::Unit)
+    }
+
+    fn auth_magic(&self, magic: u32) -> Result<()> {
+        let call = TraceCall::AuthMagic {
+            magic,
+        };
+
+        let ret = self.inner.auth_magic(magic);
+        self.record(call, ret, |_| TraceReply::Unit)
+    }
+
+    fn create_lease(
+        &self,
+        objects: &[u32],
+        flags: libc::c_int,
+    ) -> Result<(u32, OwnedFd)> {
+        let call = TraceCall::CreateLease {
+            objects: objects.to_vec(),
+            flags,
+        };
+
+        let ret = self.inner.create_lease(objects, flags);
+        self.record(call, ret, |(lessee_id, _)| TraceReply::Id(*lessee_id))
+    }
+
+    fn create_property_blob(&self, data: &[u8]) -> Result<u32> {
+        let call = TraceCall::CreatePropertyBlob {
+            data: data.to_vec(),
+        };
+
+        let ret = self.inner.create_property_blob(data);
+        self.record(call, ret, |id| TraceReply::Id(*id))
+    }
+
+    fn destroy_property_blob(&self, id: u32) -> Result<()> {
+        let call = TraceCall::DestroyPropertyBlob {
+            id,
+        };
+
+        let ret = self.inner.destroy_property_blob(id);
+        self.record(call, ret, |_| TraceReply::Unit)
+    }
+
+    fn remove_framebuffer(&self, id: u32) {
+        let call = TraceCall::RemoveFramebuffer {
+            id,
+        };
+
+        self.inner.remove_framebuffer(id);
+        let _ = self.record(call, Ok(()), |_| TraceReply::Unit);
+    }
+
+    fn drop_master(&self) -> Result<()> {
+        let ret = self.inner.drop_master();
+        self.record(TraceCall::DropMaster, ret, |_| TraceReply::Unit)
+    }
+
+    fn destroy_dumb_buffer(&self, handle: u32) {
+        let call = TraceCall::DestroyDumbBuffer {
+            handle,
+        };
+
+        self.inner.destroy_dumb_buffer(handle);
+        let _ = self.record(call, Ok(()), |_| TraceReply::Unit);
+    }
+
+    fn get_capability(&self, cap: u64) -> Result<u64> {
+        let call = TraceCall::GetCapability {
+            capability: cap,
+        };
+
+        let ret = self.inner.get_capability(cap);
+        self.record(call, ret, |val| TraceReply::Value(*val))
+    }
+
+    fn get_driver_version(
+        &self,
+        name: &mut Vec<u8>,
+        date: &mut Vec<u8>,
+        desc: &mut Vec<u8>,
+    ) -> Result<drm_version> {
+        let ret = self.inner.get_driver_version(name, date, desc);
+        self.record(TraceCall::GetDriverVersion, ret, |version| {
+            TraceReply::Version {
+                version: version.clone(),
+                name:    name.clone(),
+                date:    date.clone(),
+                desc:    desc.clone(),
+            }
+        })
+    }
+
+    fn get_encoder(&self, id: u32) -> Result<drm_mode_get_encoder> {
+        let call = TraceCall::GetEncoder {
+            id,
+        };
+
+        let ret = self.inner.get_encoder(id);
+        self.record(call, ret, |encoder| TraceReply::Encoder(encoder.clone()))
+    }
+
+    fn get_connector(
+        &self,
+        id: u32,
+        mut modes: Option<&mut Vec<drm_mode_modeinfo>>,
+        mut encoders: Option<&mut Vec<u32>>,
+        mut properties: Option<&mut Vec<u32>>,
+    ) -> Result<drm_mode_get_connector> {
+        let call = TraceCall::GetConnector {
+            id,
+            modes: modes.is_some(),
+            encoders: encoders.is_some(),
+            properties: properties.is_some(),
+        };
+
+        let ret = self.inner.get_connector(
+            id,
+            modes.as_deref_mut(),
+            encoders.as_deref_mut(),
+            properties.as_deref_mut(),
+        );
+        self.record(call, ret, |connector| TraceReply::Connector {
+            connector:  connector.clone(),
+            modes:      modes.map(|v| v.clone()).unwrap_or_default(),
+            encoders:   encoders.map(|v| v.clone()).unwrap_or_default(),
+            properties: properties.map(|v| v.clone()).unwrap_or_default(),
+        })
+    }
+
+    fn get_connectors(&self) -> Result<Vec<drm_mode_get_connector>> {
+        let ret = self.inner.get_connectors();
+        self.record(TraceCall::GetConnectors, ret, |connectors| {
+            TraceReply::Connectors(connectors.clone())
+        })
+    }
+
+    fn get_crtcs(&self) -> Result<Vec<drm_mode_crtc>> {
+        let ret = self.inner.get_crtcs();
+        self.record(TraceCall::GetCrtcs, ret, |crtcs| {
+            TraceReply::Crtcs(crtcs.clone())
+        })
+    }
+
+    fn get_plane(&self, id: u32) -> Result<drm_mode_get_plane> {
+        let call = TraceCall::GetPlane {
+            id,
+        };
+
+        let ret = self.inner.get_plane(id);
+        self.record(call, ret, |plane| TraceReply::Plane(plane.clone()))
+    }
+
+    fn get_planes(&self) -> Result<Vec<drm_mode_get_plane>> {
+        let ret = self.inner.get_planes();
+        self.record(TraceCall::GetPlanes, ret, |planes| {
+            TraceReply::Planes(planes.clone())
+        })
+    }
+
+    fn get_lease(&self) -> Result<Vec<u32>> {
+        let ret = self.inner.get_lease();
+        self.record(TraceCall::GetLease, ret, |ids| {
+            TraceReply::Ids(ids.clone())
+        })
+    }
+
+    fn get_magic(&self) -> Result<u32> {
+        let ret = self.inner.get_magic();
+        self.record(TraceCall::GetMagic, ret, |magic| TraceReply::Id(*magic))
+    }
+
+    fn get_property(
+        &self,
+        id: u32,
+        mut values: Option<&mut Vec<u64>>,
+        mut enums: Option<&mut Vec<drm_mode_property_enum>>,
+    ) -> Result<drm_mode_get_property> {
+        let call = TraceCall::GetProperty {
+            id,
+            values: values.is_some(),
+            enums: enums.is_some(),
+        };
+
+        let ret = self.inner.get_property(
+            id,
+            values.as_deref_mut(),
+            enums.as_deref_mut(),
+        );
+        self.record(call, ret, |property| TraceReply::Property {
+            property: property.clone(),
+            values:   values.map(|v| v.clone()).unwrap_or_default(),
+            enums:    enums.map(|v| v.clone()).unwrap_or_default(),
+        })
+    }
+
+    fn get_properties(
+        &self,
+        object_type: u32,
+        object_id: u32,
+    ) -> Result<Vec<(u32, u64)>> {
+        let call = TraceCall::GetProperties {
+            object_type,
+            object_id,
+        };
+
+        let ret = self.inner.get_properties(object_type, object_id);
+        self.record(call, ret, |props| TraceReply::Properties(props.clone()))
+    }
+
+    fn get_property_blob(&self, id: u32) -> Result<Vec<u8>> {
+        let call = TraceCall::GetPropertyBlob {
+            id,
+        };
+
+        let ret = self.inner.get_property_blob(id);
+        self.record(call, ret, |data| TraceReply::Bytes(data.clone()))
+    }
+
+    fn is_master(&self) -> Result<bool> {
+        let ret = self.inner.is_master();
+        self.record(TraceCall::IsMaster, ret, |master| {
+            TraceReply::Bool(*master)
+        })
+    }
+
+    fn list_lessees(&self) -> Result<Vec<u32>> {
+        let ret = self.inner.list_lessees();
+        self.record(TraceCall::ListLessees, ret, |ids| {
+            TraceReply::Ids(ids.clone())
+        })
+    }
+
+    fn map_dumb_buffer(&self, handle: u32, size: usize) -> Result<MemoryMap> {
+        let call = TraceCall::MapDumbBuffer {
+            handle,
+            size,
+        };
+
+        let ret = self.inner.map_dumb_buffer(handle, size);
+        self.record(call, ret, |_| TraceReply::Unit)
+    }
+
+    fn read_events(&self, buf: &mut [u8]) -> Result<usize> {
+        let call = TraceCall::ReadEvents {
+            len: buf.len(),
+        };
+
+        let ret = self.inner.read_events(buf);
+        self.record(call, ret, |len| TraceReply::Bytes(buf[..*len].to_vec()))
+    }
+
+    fn revoke_lease(&self, lessee_id: u32) -> Result<()> {
+        let call = TraceCall::RevokeLease {
+            lessee_id,
+        };
+
+        let ret = self.inner.revoke_lease(lessee_id);
+        self.record(call, ret, |_| TraceReply::Unit)
+    }
+
+    fn set_crtc(
+        &self,
+        fb_id: u32,
+        crtc_id: u32,
+        connectors: &[u32],
+        x: u32,
+        y: u32,
+        mode: Option<&drm_mode_modeinfo>,
+    ) -> Result<()> {
+        let call = TraceCall::SetCrtc {
+            fb_id,
+            crtc_id,
+            connectors: connectors.to_vec(),
+            x,
+            y,
+            mode: mode.copied(),
+        };
+
+        let ret = self.inner.set_crtc(fb_id, crtc_id, connectors, x, y, mode);
+        self.record(call, ret, |_| TraceReply::Unit)
+    }
+
+    fn set_client_capability(&self, cap: u64) -> Result<()> {
+        let call = TraceCall::SetClientCapability {
+            capability: cap,
+        };
+
+        let ret = self.inner.set_client_capability(cap);
+        self.record(call, ret, |_| TraceReply::Unit)
+    }
+
+    fn set_master(&self) -> Result<()> {
+        let ret = self.inner.set_master();
+        self.record(TraceCall::SetMaster, ret, |_| TraceReply::Unit)
+    }
+
+    fn set_plane(
+        &self,
+        fb_id: u32,
+        plane_id: u32,
+        crtc_id: u32,
+        w: u32,
+        h: u32,
+    ) -> Result<()> {
+        let call = TraceCall::SetPlane {
+            fb_id,
+            plane_id,
+            crtc_id,
+            w,
+            h,
+        };
+
+        let ret = self.inner.set_plane(fb_id, plane_id, crtc_id, w, h);
+        self.record(call, ret, |_| TraceReply::Unit)
+    }
+
+    fn take_trace(&self) -> Option<Trace> {
+        let entries = std::mem::take(&mut *lock(&self.entries));
+
+        Some(Trace {
+            entries,
+        })
+    }
+}
+
+fn diverged(call: &TraceCall) -> Error {
+    Error::TraceDivergenceError {
+        ioctl: call.get_ioctl().to_string(),
+    }
+}
+
+#[derive(Debug)]
+struct ReplayState {
+    entries: Vec<TraceEntry>,
+    next:    usize,
+}
+
+#[derive(Debug)]
+struct ReplayBackend {
+    state: Mutex<ReplayState>,
+    fd:    OwnedFd,
+}
+
+impl ReplayBackend {
+    fn new(trace: Trace) -> Result<ReplayBackend> {
+        let fd = cvt(unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) })?;
+
+        Ok(ReplayBackend {
+            state: Mutex::new(ReplayState {
+                entries: trace.entries,
+                next: 0,
+            }),
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    // Hand out the recorded answers in the order they were recorded. Any
+    // other call means the replay diverged from the traced program.
+    fn replay(&self, call: &TraceCall) -> Result<TraceReply> {
+        let mut state = lock(&self.state);
+        let next = state.next;
+
+        match state.entries.get(next) {
+            Some(entry) if entry.call == *call => {},
+            _ => return Err(diverged(call)),
+        }
+
+        state.next += 1;
+        state.entries[next].result.clone()
+    }
+
+    fn replay_unit(&self, call: TraceCall) -> Result<()> {
+        match self.replay(&call)? {
+            TraceReply::Unit => Ok(()),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn replay_id(&self, call: TraceCall) -> Result<u32> {
+        match self.replay(&call)? {
+            TraceReply::Id(id) => Ok(id),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn replay_ids(&self, call: TraceCall) -> Result<Vec<u32>> {
+        match self.replay(&call)? {
+            TraceReply::Ids(ids) => Ok(ids),
+            _ => Err(diverged(&call)),
+        }
+    }
+}
+
+impl Backend for ReplayBackend {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+
+    fn allocate_dumb_buffer(
+        &self,
+        width: usize,
+        height: usize,
+        bpp: usize,
+    ) -> Result<drm_mode_create_dumb> {
+        let call = TraceCall::CreateDumbBuffer {
+            width,
+            height,
+            bpp,
+        };
+
+        match self.replay(&call)? {
+            TraceReply::DumbBuffer(dumb) => Ok(dumb),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn add_framebuffer(
+        &self,
+        handle: u32,
+        width: u32,
+        pitch: u32,
+        height: u32,
+        fmt: u32,
+    ) -> Result<u32> {
+        self.replay_id(TraceCall::AddFramebuffer {
+            handle,
+            width,
+            pitch,
+            height,
+            format: fmt,
+        })
+    }
+
+    fn atomic_commit(
+        &self,
+        objs_ptr: Vec<u32>,
+        count_props_ptr: Vec<u32>,
+        props_ptr: Vec<u32>,
+        prop_values_ptr: Vec<u64>,
+        flags: u32,
+        user_data: u64,
+    ) -> Result<()> {
+        self.replay_unit(TraceCall::AtomicCommit {
+            objects:     objs_ptr,
+            count_props: count_props_ptr,
+            props:       props_ptr,
+            values:      prop_values_ptr,
+            flags,
+            user_data,
+        })
+    }
+
+    fn auth_magic(&self, magic: u32) -> Result<()> {
+        self.replay_unit(TraceCall::AuthMagic {
+            magic,
+        })
+    }
+
+    fn create_lease(
+        &self,
+        objects: &[u32],
+        flags: libc::c_int,
+    ) -> Result<(u32, OwnedFd)> {
+        // The lease file descriptor can't be brought back to life
+        self.replay_id(TraceCall::CreateLease {
+            objects: objects.to_vec(),
+            flags,
+        })?;
+
+        Err(Error::UnsupportedError)
+    }
+
+    fn create_property_blob(&self, data: &[u8]) -> Result<u32> {
+        self.replay_id(TraceCall::CreatePropertyBlob {
+            data: data.to_vec(),
+        })
+    }
+
+    fn destroy_property_blob(&self, id: u32) -> Result<()> {
+        self.replay_unit(TraceCall::DestroyPropertyBlob {
+            id,
+        })
+    }
+
+    fn remove_framebuffer(&self, id: u32) {
+        let _ = self.replay_unit(TraceCall::RemoveFramebuffer {
+            id,
+        });
+    }
+
+    fn drop_master(&self) -> Result<()> {
+        self.replay_unit(TraceCall::DropMaster)
+    }
+
+    fn destroy_dumb_buffer(&self, handle: u32) {
+        let _ = self.replay_unit(TraceCall::DestroyDumbBuffer {
+            handle,
+        });
+    }
+
+    fn get_capability(&self, cap: u64) -> Result<u64> {
+        let call = TraceCall::GetCapability {
+            capability: cap,
+        };
+
+        match self.replay(&call)? {
+            TraceReply::Value(val) => Ok(val),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_driver_version(
+        &self,
+        name: &mut Vec<u8>,
+        date: &mut Vec<u8>,
+        desc: &mut Vec<u8>,
+    ) -> Result<drm_version> {
+        let call = TraceCall::GetDriverVersion;
+
+        match self.replay(&call)? {
+            TraceReply::Version {
+                version,
+                name: rec_name,
+                date: rec_date,
+                desc: rec_desc,
+            } => {
+                *name = rec_name;
+                *date = rec_date;
+                *desc = rec_desc;
+
+                Ok(version)
+            },
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_encoder(&self, id: u32) -> Result<drm_mode_get_encoder> {
+        let call = TraceCall::GetEncoder {
+            id,
+        };
+
+        match self.replay(&call)? {
+            TraceReply::Encoder(encoder) => Ok(encoder),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_connector(
+        &self,
+        id: u32,
+        modes: Option<&mut Vec<drm_mode_modeinfo>>,
+        encoders: Option<&mut Vec<u32>>,
+        properties: Option<&mut Vec<u32>>,
+    ) -> Result<drm_mode_get_connector> {
+        let call = TraceCall::GetConnector {
+            id,
+            modes: modes.is_some(),
+            encoders: encoders.is_some(),
+            properties: properties.is_some(),
+        };
+
+        match self.replay(&call)? {
+            TraceReply::Connector {
+                connector,
+                modes: rec_modes,
+                encoders: rec_encoders,
+                properties: rec_properties,
+            } => {
+                if let Some(modes) = modes {
+                    *modes = rec_modes;
+                }
+
+                if let Some(encoders) = encoders {
+                    *encoders = rec_encoders;
+                }
+
+                if let Some(properties) = properties {
+                    *properties = rec_properties;
+                }
+
+                Ok(connector)
+            },
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_connectors(&self) -> Result<Vec<drm_mode_get_connector>> {
+        let call = TraceCall::GetConnectors;
+
+        match self.replay(&call)? {
+            TraceReply::Connectors(connectors) => Ok(connectors),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_crtcs(&self) -> Result<Vec<drm_mode_crtc>> {
+        let call = TraceCall::GetCrtcs;
+
+        match self.replay(&call)? {
+            TraceReply::Crtcs(crtcs) => Ok(crtcs),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_plane(&self, id: u32) -> Result<drm_mode_get_plane> {
+        let call = TraceCall::GetPlane {
+            id,
+        };
+
+        match self.replay(&call)? {
+            TraceReply::Plane(plane) => Ok(plane),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_planes(&self) -> Result<Vec<drm_mode_get_plane>> {
+        let call = TraceCall::GetPlanes;
+
+        match self.replay(&call)? {
+            TraceReply::Planes(planes) => Ok(planes),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_lease(&self) -> Result<Vec<u32>> {
+        self.replay_ids(TraceCall::GetLease)
+    }
+
+    fn get_magic(&self) -> Result<u32> {
+        self.replay_id(TraceCall::GetMagic)
+    }
+
+    fn get_property(
+        &self,
+        id: u32,
+        values: Option<&mut Vec<u64>>,
+        enums: Option<&mut Vec<drm_mode_property_enum>>,
+    ) -> Result<drm_mode_get_property> {
+        let call = TraceCall::GetProperty {
+            id,
+            values: values.is_some(),
+            enums: enums.is_some(),
+        };
+
+        match self.replay(&call)? {
+            TraceReply::Property {
+                property,
+                values: rec_values,
+                enums: rec_enums,
+            } => {
+                if let Some(values) = values {
+                    *values = rec_values;
+                }
+
+                if let Some(enums) = enums {
+                    *enums = rec_enums;
+                }
+
+                Ok(property)
+            },
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_properties(
+        &self,
+        object_type: u32,
+        object_id: u32,
+    ) -> Result<Vec<(u32, u64)>> {
+        let call = TraceCall::GetProperties {
+            object_type,
+            object_id,
+        };
+
+        match self.replay(&call)? {
+            TraceReply::Properties(props) => Ok(props),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn get_property_blob(&self, id: u32) -> Result<Vec<u8>> {
+        let call = TraceCall::GetPropertyBlob {
+            id,
+        };
+
+        match self.replay(&call)? {
+            TraceReply::Bytes(data) => Ok(data),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn is_master(&self) -> Result<bool> {
+        let call = TraceCall::IsMaster;
+
+        match self.replay(&call)? {
+            TraceReply::Bool(master) => Ok(master),
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn list_lessees(&self) -> Result<Vec<u32>> {
+        self.replay_ids(TraceCall::ListLessees)
+    }
+
+    fn map_dumb_buffer(&self, handle: u32, size: usize) -> Result<MemoryMap> {
+        self.replay_unit(TraceCall::MapDumbBuffer {
+            handle,
+            size,
+        })?;
+
+        let mapping = MemoryMap::new(size, &[
+            MapOption::MapReadable,
+            MapOption::MapWritable,
+        ])?;
+
+        Ok(mapping)
+    }
+
+    fn read_events(&self, buf: &mut [u8]) -> Result<usize> {
+        let call = TraceCall::ReadEvents {
+            len: buf.len(),
+        };
+
+        match self.replay(&call)? {
+            TraceReply::Bytes(data) => {
+                buf[..data.len()].copy_from_slice(&data);
+                Ok(data.len())
+            },
+            _ => Err(diverged(&call)),
+        }
+    }
+
+    fn revoke_lease(&self, lessee_id: u32) -> Result<()> {
+        self.replay_unit(TraceCall::RevokeLease {
+            lessee_id,
+        })
+    }
+
+    fn set_crtc(
+        &self,
+        fb_id: u32,
+        crtc_id: u32,
+        connectors: &[u32],
+        x: u32,
+        y: u32,
+        mode: Option<&drm_mode_modeinfo>,
+    ) -> Result<()> {
+        self.replay_unit(TraceCall::SetCrtc {
+            fb_id,
+            crtc_id,
+            connectors: connectors.to_vec(),
+            x,
+            y,
+            mode: mode.copied(),
+        })
+    }
+
+    fn set_client_capability(&self, cap: u64) -> Result<()> {
+        self.replay_unit(TraceCall::SetClientCapability {
+            capability: cap,
+        })
+    }
+
+    fn set_master(&self) -> Result<()> {
+        self.replay_unit(TraceCall::SetMaster)
+    }
+
+    fn set_plane(
+        &self,
+        fb_id: u32,
+        plane_id: u32,
+        crtc_id: u32,
+        w: u32,
+        h: u32,
+    ) -> Result<()> {
+        self.replay_unit(TraceCall::SetPlane {
+            fb_id,
+            plane_id,
+            crtc_id,
+            w,
+            h,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rawdevice::DRM_MODE_ATOMIC_ALLOW_MODESET;
+    use crate::simulated::fixtures;
+    use crate::ConnectorStatus;
+    use crate::ConnectorType;
+    use crate::Device;
+    use crate::Error;
+    use crate::Object;
+    use crate::PipelineInit;
+    use crate::SimulatedDevice;
+
+    use super::Trace;
+    use super::TraceCall;
+
+    fn discover(dev: &Device) -> (u32, u32, u32) {
+        let connector = dev
+            .get_connectors()
+            .unwrap()
+            .into_iter()
+            .find(|con| con.get_status() == ConnectorStatus::Connected)
+            .unwrap();
+
+        let encoder = connector.get_encoders().unwrap().remove(0);
+        let crtc = encoder.get_possible_crtcs().unwrap().remove(0);
+        let plane = crtc.get_possible_planes().unwrap().remove(0);
+        let ids = (connector.get_id(), crtc.get_id(), plane.get_id());
+
+        PipelineInit::new(dev).discover(connector).unwrap();
+
+        ids
+    }
+
+    fn record() -> (Trace, (u32, u32, u32)) {
+        let dev = SimulatedDevice::new()
+            .add_output(
+                ConnectorType::DisplayPort,
+                ConnectorStatus::Disconnected,
+                1280,
+                720,
+                60,
+            )
+            .add_output(
+                ConnectorType::HDMIA,
+                ConnectorStatus::Connected,
+                1920,
+                1080,
+                60,
+            )
+            .add_overlay_plane(3)
+            .set_tracing(true)
+            .into_device()
+            .unwrap();
+
+        let ids = discover(&dev);
+        let trace = dev.take_trace().unwrap();
+
+        (trace, ids)
+    }
+
+    #[test]
+    fn test_trace_replay_discover() {
+        let (trace, ids) = record();
+
+        assert!(!trace.is_empty());
+        assert!(trace.iter().any(|entry| entry.get_ioctl() == "GETCONNECTOR"));
+        assert!(trace.iter().all(|entry| !entry.is_error()));
+
+        let dev = trace.into_device().unwrap();
+        assert_eq!(discover(&dev), ids);
+        assert!(dev.take_trace().is_none());
+    }
+
+    #[test]
+    fn test_trace_replay_exhausted() {
+        let (trace, _) = record();
+
+        let dev = trace.into_device().unwrap();
+        discover(&dev);
+
+        // The replay has nothing left to answer with
+        assert_eq!(
+            dev.get_connectors().unwrap_err(),
+            Error::TraceDivergenceError {
+                ioctl: String::from("GETRESOURCES"),
+            }
+        );
+    }
+
+    #[test]
+    fn test_trace_replay_order() {
+        let (trace, _) = record();
+
+        // The planes were only listed once the connectors had been
+        let dev = trace.into_device().unwrap();
+        assert_eq!(
+            dev.get_planes().unwrap_err(),
+            Error::TraceDivergenceError {
+                ioctl: String::from("GETPLANERESOURCES"),
+            }
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_trace_serialize() {
+        let (trace, ids) = record();
+
+        let json = serde_json::to_string(&trace).unwrap();
+        let trace: Trace = serde_json::from_str(&json).unwrap();
+
+        let dev = trace.into_device().unwrap();
+        assert_eq!(discover(&dev), ids);
+    }
+
+    #[test]
+    fn test_trace_pipeline_mode_reuse() {
+        let dev = fixtures::hdmi_output()
+            .set_tracing(true)
+            .into_device()
+            .unwrap();
+        let buffer = fixtures::framebuffer(&dev);
+
+        let build = || fixtures::pipeline_init(&dev, &buffer).build().unwrap();
+
+        let commits = |trace: Trace| -> Vec<u32> {
+            trace
+                .entries
+                .into_iter()
+                .filter_map(|entry| match entry.call {
+                    TraceCall::AtomicCommit {
+                        flags,
+                        ..
+                    } => Some(flags),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let _first = build();
+        let trace = dev.take_trace().unwrap();
+        let blobs = trace
+            .iter()
+            .filter(|entry| entry.get_ioctl() == "CREATEPROPBLOB")
+            .count();
+        assert_eq!(blobs, 1);
+        assert_eq!(commits(trace), vec![DRM_MODE_ATOMIC_ALLOW_MODESET]);
+
+        // Same mode on the same output: the blob is shared, no modeset
+        let second = build();
+        let trace = dev.take_trace().unwrap();
+        assert!(trace
+            .iter()
+            .all(|entry| entry.get_ioctl() != "CREATEPROPBLOB"));
+        assert_eq!(commits(trace), vec![0]);
+
+        // Flipping to another buffer doesn't need a modeset either
+        let _second = second
+            .update_buffer(buffer.clone())
+            .unwrap()
+            .commit()
+            .unwrap();
+        assert_eq!(commits(dev.take_trace().unwrap()), vec![0]);
+    }
+}